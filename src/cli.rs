@@ -1,4 +1,5 @@
 use std::env;
+use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 use clap_complete::engine::ArgValueCompleter;
@@ -9,18 +10,39 @@ use crate::{
     config::{Config, Project},
     devcontainer::DevContainer,
     docker::DockerClient,
+    engine::Engine,
     worktree,
 };
 
 mod compose;
-mod copy;
+pub(crate) mod copy;
 mod destroy;
 mod exec;
 mod fwd;
 mod list;
+mod lock;
+mod prune;
+mod rename;
+mod repair;
 mod show;
+mod stop;
+mod trash;
+mod tui;
 pub(crate) mod up;
 
+/// An error that just means to exit with the given code, having already said what there is to say,
+/// as `dc show status` does when the workspace isn't running.
+#[derive(Debug)]
+pub struct ExitCode(pub i32);
+
+impl std::fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "exit code {}", self.0)
+    }
+}
+
+impl std::error::Error for ExitCode {}
+
 const ABOUT: &str =
     "A tool for managing devcontainers, especially when combined with git worktrees";
 
@@ -56,7 +78,23 @@ pub enum Commands {
     #[command(visible_alias = "c")]
     Compose(compose::Compose),
     #[command()]
+    Stop(stop::Stop),
+    #[command()]
     Destroy(destroy::Destroy),
+    #[command()]
+    Prune(prune::Prune),
+    #[command()]
+    Rename(rename::Rename),
+    #[command()]
+    Repair(repair::Repair),
+    #[command()]
+    Lock(lock::Lock),
+    #[command()]
+    Unlock(lock::Unlock),
+    #[command()]
+    Trash(trash::Trash),
+    #[command()]
+    Tui(tui::Tui),
     // Temporarily disabled as we try to copy while running.
     // #[command()]
     // Copy(copy::Copy),
@@ -71,6 +109,25 @@ pub struct State {
 }
 
 impl State {
+    /// Connect to the project's engine, using its docker context if it has one.
+    pub async fn new(
+        engine: Engine,
+        project_name: String,
+        project: Project,
+        workspace: Option<String>,
+    ) -> eyre::Result<Self> {
+        let engine = match &project.context {
+            Some(context) => engine.with_context(context.clone())?,
+            None => engine,
+        };
+        Ok(State {
+            docker: DockerClient::new(engine).await?,
+            project_name,
+            project,
+            workspace,
+        })
+    }
+
     // TODO: We should just load this at start.
     fn devcontainer(&self) -> eyre::Result<DevContainer> {
         DevContainer::load(&self.project)
@@ -83,22 +140,39 @@ impl State {
             .is_some_and(|root| name == root)
     }
 
-    /// If a name was given, return it. Otherwise, return the name of the
+    /// The path of the named workspace's worktree, whether or not it exists.
+    pub fn worktree_path(&self, dc: &DevContainer, name: &str) -> PathBuf {
+        if self.is_root(name) {
+            self.project.path.clone()
+        } else {
+            let dc_options = &dc.common.customizations.dc;
+            dc_options.workspace_dir(&self.project.path).join(name)
+        }
+    }
+
+    /// If a name was given, return the workspace it names. Otherwise, return the name of the
     /// worktree we're currently inside.
+    ///
+    /// A workspace's name is its worktree's directory name, but it may also be given by its branch,
+    /// or in the branch-style form it was created with, e.g. `user/thing` for `user-thing`.
     pub async fn resolve_workspace(&self) -> eyre::Result<String> {
+        let worktrees = worktree::list(&self.project.path).await?;
+
         if let Some(n) = &self.workspace {
-            return Ok(n.clone());
+            return resolve_name(&worktrees, n);
         }
 
         let cwd = env::current_dir()?;
-        let worktrees = worktree::list(&self.project.path).await?;
 
-        worktrees.into_iter().find(|wt| wt == &cwd).ok_or_else(|| {
-            eyre::eyre!(
-                "no workspace specified and not inside a worktree of project '{}'",
-                self.project_name
-            )
-        })?;
+        worktrees
+            .into_iter()
+            .find(|wt| wt.path == cwd)
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "no workspace specified and not inside a worktree of project '{}'",
+                    self.project_name
+                )
+            })?;
 
         Ok(cwd
             .file_name()
@@ -106,19 +180,59 @@ impl State {
             .to_string_lossy()
             .to_string())
     }
+
+    /// Resolve the given workspace name, as `resolve_workspace` does the `--workspace` one.
+    pub async fn resolve(&self, name: &str) -> eyre::Result<String> {
+        let worktrees = worktree::list(&self.project.path).await?;
+        resolve_name(&worktrees, name)
+    }
+
+    /// Resolve each of the given workspace names, as `resolve_workspace` does the `--workspace`
+    /// one.
+    pub async fn resolve_workspaces(&self, names: &[String]) -> eyre::Result<Vec<String>> {
+        let worktrees = worktree::list(&self.project.path).await?;
+        names.iter().map(|n| resolve_name(&worktrees, n)).collect()
+    }
+}
+
+/// The name of the workspace that `n` names, by its worktree's directory name, the name it was
+/// created with, or its branch.
+fn resolve_name(worktrees: &[worktree::Worktree], n: &str) -> eyre::Result<String> {
+    let dir_name = |wt: &worktree::Worktree| {
+        wt.path
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+    };
+
+    if worktrees
+        .iter()
+        .any(|wt| dir_name(wt).as_deref() == Some(n))
+    {
+        return Ok(n.to_string());
+    }
+    if let Some(name) = worktrees
+        .iter()
+        .find(|wt| wt.name.as_deref() == Some(n))
+        .or_else(|| worktrees.iter().find(|wt| wt.branch.as_deref() == Some(n)))
+        .and_then(dir_name)
+    {
+        return Ok(name);
+    }
+    worktree::dir_name(n)
 }
 
 impl Cli {
     pub async fn run(self) -> eyre::Result<()> {
         let config = Config::load()?;
-        let (project_name, project) = config.project(self.project)?;
+        if let Commands::List(list) = &self.command
+            && list.all_projects
+        {
+            return list.run_all(config).await;
+        }
 
-        let state = State {
-            docker: DockerClient::new().await?,
-            project_name,
-            project,
-            workspace: self.workspace,
-        };
+        let engine = config.engine.clone();
+        let (project_name, project) = config.project(self.project)?;
+        let state = State::new(engine, project_name, project, self.workspace).await?;
 
         match self.command {
             Commands::Up(up) => up.run(state).await,
@@ -128,7 +242,15 @@ impl Cli {
             Commands::Compose(compose) => compose.run(state).await,
             // Commands::Copy(copy) => copy.run(state).await,
             Commands::Show(show) => show.run(state).await,
+            Commands::Stop(stop) => stop.run(state).await,
             Commands::Destroy(destroy) => destroy.run(state).await,
+            Commands::Prune(prune) => prune.run(state).await,
+            Commands::Rename(rename) => rename.run(state).await,
+            Commands::Repair(repair) => repair.run(state).await,
+            Commands::Lock(lock) => lock.run(state).await,
+            Commands::Unlock(unlock) => unlock.run(state).await,
+            Commands::Trash(trash) => trash.run(state).await,
+            Commands::Tui(tui) => tui.run(state).await,
         }
     }
 }
@@ -4,10 +4,10 @@ use clap::Args;
 use clap_complete::engine::ArgValueCompleter;
 
 use crate::cli::State;
-use crate::cli::up::compose_base_args;
+use crate::cli::up::{ComposeExtras, compose_base_args};
 use crate::complete;
 
-/// Run `docker compose` against the given workspace
+/// Run `docker compose` (or your engine's equivalent) against the given workspace
 #[derive(Debug, Args)]
 pub struct Compose {
     /// Arguments to provide to `docker compose`
@@ -23,18 +23,15 @@ impl Compose {
             unimplemented!();
         };
 
-        let worktree_path = if state.is_root(&name) {
-            state.project.path.clone()
-        } else {
-            let dc_options = &dc.common.customizations.dc;
-            dc_options.workspace_dir(&state.project.path).join(&name)
-        };
+        let worktree_path = state.worktree_path(&dc, &name);
+        let extras = ComposeExtras::recorded(&state, &worktree_path).await?;
 
-        let mut args = compose_base_args(compose, &worktree_path, None);
+        let mut args =
+            compose_base_args(&state.docker.engine, compose, &worktree_path, None, &extras);
         args.extend(self.args);
 
-        Err(std::process::Command::new("docker")
-            .args(&args)
+        Err(std::process::Command::new(&args[0])
+            .args(&args[1..])
             .exec()
             .into())
     }
@@ -89,18 +89,16 @@ impl Runnable for CopyVolume<'_> {
     }
 
     async fn run(self, _: crate::run::Token) -> eyre::Result<()> {
-        do_copy_volume(
-            self.docker,
-            &self.src,
-            &self.dst,
-            &self.to_project,
-            &self.name,
-        )
-        .await
+        // Label the destination volume like docker compose would, so that it manages it.
+        let labels = HashMap::from([
+            ("com.docker.compose.project".into(), self.to_project),
+            ("com.docker.compose.volume".into(), self.name),
+        ]);
+        do_copy_volume(self.docker, &self.src, &self.dst, labels).await
     }
 }
 
-const IMAGE: &str = "docker.io/library/alpine:latest";
+pub(crate) const IMAGE: &str = "docker.io/library/alpine:latest";
 
 async fn ensure_image(docker: &Docker) -> eyre::Result<()> {
     if docker.inspect_image(IMAGE).await.is_ok() {
@@ -117,21 +115,15 @@ async fn ensure_image(docker: &Docker) -> eyre::Result<()> {
     Ok(())
 }
 
-async fn do_copy_volume(
+/// Copy the contents of volume `src` to `dst`, which is created with `labels`.
+pub(crate) async fn do_copy_volume(
     docker: &Docker,
     src: &str,
     dst: &str,
-    project: &str,
-    vol_name: &str,
+    labels: HashMap<String, String>,
 ) -> eyre::Result<()> {
     ensure_image(docker).await?;
 
-    // Pre-create destination volume with docker compose labels so that it recognizes the volume and
-    // will manage it.
-    let labels = HashMap::from([
-        ("com.docker.compose.project".into(), project.to_string()),
-        ("com.docker.compose.volume".into(), vol_name.to_string()),
-    ]);
     docker
         .create_volume(VolumeCreateRequest {
             name: Some(dst.to_string()),
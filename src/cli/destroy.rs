@@ -1,70 +1,333 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
 use std::io::{BufRead, Write};
 use std::path::Path;
 
-use bollard::Docker;
-use bollard::query_parameters::{ListContainersOptions, RemoveContainerOptions};
 use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
 use eyre::{Context, eyre};
 
 use crate::ansi::{RED, RESET, YELLOW};
 use crate::cli::State;
+use crate::cli::fwd::{close_tunnel, container_names, has_tunnel, remove_containers};
+use crate::cli::up::override_file;
+use crate::complete;
+use crate::docker::DockerClient;
 use crate::run::{self, Runnable, Runner, run_cmd};
+use crate::trash;
 use crate::workspace::Workspace;
+use crate::workspace::git_status::{Merged, base_branch};
+use crate::worktree;
 
-/// Fully destroy the workspace; equivalent to `docker compose down -v --remove-orphans && git worktree remove`
+/// Fully destroy workspaces; equivalent to `docker compose down -v --remove-orphans && git worktree remove`
 #[derive(Debug, Args)]
 pub struct Destroy {
-    /// force remove the worktree, even if dirty
+    /// The workspaces to destroy [default: the --workspace one, then the current one]
+    #[arg(add = ArgValueCompleter::new(complete::complete_workspace))]
+    workspaces: Vec<String>,
+
+    /// Destroy every workspace, except the root one and locked ones, whose containers are all
+    /// stopped, or that has none
+    #[arg(long, conflicts_with = "workspaces")]
+    all_stopped: bool,
+
+    /// Show what would be removed, without removing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Don't ask for confirmation
+    #[arg(short, long)]
+    yes: bool,
+
+    /// force remove the worktree, even if dirty or locked, and delete its branch, even if unmerged
     #[arg(short, long)]
     force: bool,
+
+    /// Delete the workspace's branch too [default: the project's delete_branch]
+    #[arg(long, conflicts_with = "keep_branch")]
+    delete_branch: bool,
+
+    /// Keep the workspace's branch, even if the project's delete_branch is set
+    #[arg(long)]
+    keep_branch: bool,
+
+    /// Delete the branch on the remote that the workspace's branch tracks too, if the remote is on
+    /// this machine, such as a bare repository; implies --delete-branch
+    #[arg(long, conflicts_with = "keep_branch")]
+    delete_remote: bool,
+
+    /// Don't keep the workspaces in the trash, so they can't be restored
+    #[arg(long)]
+    no_trash: bool,
 }
 
 impl Destroy {
     pub async fn run(self, state: State) -> eyre::Result<()> {
-        let name = state.resolve_workspace().await?;
-        let workspace = Workspace::get(&state, &name).await?;
+        let workspaces = self.select(&state).await?;
+        if workspaces.is_empty() {
+            eprintln!("Nothing to destroy.");
+            return Ok(());
+        }
+        if !self.force
+            && let Some(ws) = workspaces.iter().find(|ws| ws.is_locked())
+        {
+            return Err(eyre!(
+                "workspace {} is locked; unlock it with `dc unlock`, or use --force",
+                ws.name
+            ));
+        }
 
-        let is_root = workspace.path == state.project.path;
+        let delete_branch = self.delete_branch
+            || self.delete_remote
+            || (state.project.delete_branch && !self.keep_branch);
+        let network = state
+            .devcontainer()?
+            .common
+            .customizations
+            .dc
+            .project_network(&state.project_name);
+        let mut cleanups = Vec::new();
+        for ws in &workspaces {
+            let mut cleanup = Cleanup::new(&state, &ws.path, self.force, network.clone());
+            cleanup.trash &= !self.no_trash;
+            cleanup.locked = ws.is_locked();
+            if delete_branch && !ws.root {
+                cleanup.branch = Branch::to_delete(&state, ws, self.force, self.delete_remote)?;
+            }
+            cleanups.push(cleanup);
+        }
 
-        if !workspace.path.exists() {
-            return Err(eyre!("no workspace named '{}' found", name));
+        let root = workspaces.iter().find(|ws| ws.root);
+        // Destroying one workspace is routine, so we only show the plan when asked, or when there's
+        // more at stake.
+        let confirm_needed = !self.yes && (root.is_some() || workspaces.len() > 1);
+        if self.dry_run || confirm_needed {
+            for cleanup in &cleanups {
+                cleanup.print_plan().await?;
+            }
+        }
+        if self.dry_run {
+            return Ok(());
         }
 
-        if is_root {
+        if let Some(root) = root {
             eprintln!(
                 "{YELLOW}Will destroy {RED}root{YELLOW} workspace — DATA WILL BE LOST{RESET}",
             );
-            if !confirm()? {
-                eprintln!("Aborted.");
-                return Ok(());
+            let dependents: Vec<_> = Workspace::list(&state)
+                .await?
+                .into_iter()
+                .filter(|ws| ws.uses_shared && ws.path != root.path)
+                .filter(|ws| !workspaces.iter().any(|w| w.path == ws.path))
+                .map(|ws| ws.name)
+                .collect();
+            if !dependents.is_empty() {
+                eprintln!(
+                    "{YELLOW}These workspaces use its shared services, and will lose them: {}{RESET}",
+                    dependents.join(", ")
+                );
             }
         }
+        if confirm_needed && !confirm()? {
+            eprintln!("Aborted.");
+            return Ok(());
+        }
+        // Expired entries are nothing to do with these workspaces, so don't stop for them.
+        if let Err(e) = trash::purge(&state).await {
+            eprintln!("{YELLOW}Failed to empty the trash: {e:#}{RESET}");
+        }
 
-        let cleanup = Cleanup {
-            docker: &state.docker.docker,
-            repo_path: &state.project.path,
-            path: &workspace.path,
-            compose_name: super::up::compose_project_name(&workspace.path),
-            remove_worktree: !is_root,
-            force: self.force,
+        run_cleanups("destroy", &cleanups).await
+    }
+
+    /// The workspaces to destroy, from their names, `--all-stopped`, or the current one.
+    async fn select(&self, state: &State) -> eyre::Result<Vec<Workspace>> {
+        if self.all_stopped {
+            let workspaces = Workspace::list(state).await?;
+            return Ok(workspaces
+                .into_iter()
+                .filter(|ws| !ws.root && !ws.is_locked() && ws.path.exists() && !ws.is_running())
+                .collect());
+        }
+
+        let mut names = if self.workspaces.is_empty() {
+            vec![state.resolve_workspace().await?]
+        } else {
+            state.resolve_workspaces(&self.workspaces).await?
         };
+        names.sort();
+        names.dedup();
 
-        Runner::run(cleanup).await
+        let mut workspaces = Workspace::list(state).await?;
+        let mut selected = Vec::new();
+        for name in names {
+            let root = state.is_root(&name);
+            let i = workspaces
+                .iter()
+                .position(|ws| ws.root == root && ws.name == name && ws.path.exists())
+                .ok_or_else(|| eyre!("no workspace named '{name}' found"))?;
+            selected.push(workspaces.swap_remove(i));
+        }
+        Ok(selected)
+    }
+}
+
+/// A branch to delete once its worktree is gone.
+struct Branch {
+    name: String,
+    /// The remote and branch there that it tracks, to delete too, if asked to and the remote is on
+    /// this machine.
+    upstream: Option<(String, String)>,
+}
+
+impl Branch {
+    /// The workspace's branch, if it has one and it's merged, or we're forcing, and with
+    /// `delete_remote`, the branch it tracks.
+    fn to_delete(
+        state: &State,
+        ws: &Workspace,
+        force: bool,
+        delete_remote: bool,
+    ) -> eyre::Result<Option<Self>> {
+        let Some(name) = &ws.git_status.branch else {
+            eprintln!(
+                "{YELLOW}Workspace {} has no branch to delete{RESET}",
+                ws.name
+            );
+            return Ok(None);
+        };
+        // A branch with no commits of its own loses nothing by being deleted. A squashed one
+        // loses its commits, though not their changes, so that needs forcing too.
+        match ws.git_status.merged {
+            Some(Merged::Yes | Merged::NoCommits) => {}
+            _ if force => {}
+            Some(Merged::Squashed) => {
+                return Err(eyre!(
+                    "branch {name} is only squash-merged into {}; use --force to delete it anyway",
+                    base_branch(&state.project)
+                ));
+            }
+            Some(Merged::No) | None => {
+                return Err(eyre!(
+                    "branch {name} is not merged into {}; use --force to delete it anyway",
+                    base_branch(&state.project)
+                ));
+            }
+        }
+        let upstream = if delete_remote {
+            worktree::local_upstream(&state.project.path, name)?
+        } else {
+            None
+        };
+        Ok(Some(Branch {
+            name: name.clone(),
+            upstream,
+        }))
     }
 }
 
-struct Cleanup<'a> {
-    docker: &'a Docker,
+pub(super) struct Cleanup<'a> {
+    docker: &'a DockerClient,
+    project: &'a str,
     repo_path: &'a Path,
     path: &'a Path,
     compose_name: String,
     remove_worktree: bool,
     force: bool,
+    /// The project network, removed once no workspace is using it.
+    network: Option<String>,
+    /// The workspace's branch, deleted after its worktree.
+    branch: Option<Branch>,
+    /// Whether to keep the workspace in the trash, so it can be restored.
+    trash: bool,
+    /// Whether the worktree is locked, which git needs forcing twice to remove.
+    locked: bool,
+}
+
+impl<'a> Cleanup<'a> {
+    /// Clean up the workspace at `path`, removing its worktree unless it's the root's.
+    pub(super) fn new(
+        state: &'a State,
+        path: &'a Path,
+        force: bool,
+        network: Option<String>,
+    ) -> Self {
+        Cleanup {
+            docker: &state.docker,
+            project: &state.project_name,
+            repo_path: &state.project.path,
+            path,
+            compose_name: super::up::compose_project_name(path),
+            remove_worktree: path != state.project.path,
+            force,
+            network,
+            branch: None,
+            trash: state.project.trash_days > 0,
+            locked: false,
+        }
+    }
+}
+
+impl Cleanup<'_> {
+    /// Print everything this would remove.
+    async fn print_plan(&self) -> eyre::Result<()> {
+        eprintln!("{}:", self.name());
+        eprintln!("  compose project {}", self.compose_name);
+        for volume in self.docker.project_volumes(&self.compose_name).await? {
+            eprintln!("  volume {}", volume.name);
+        }
+        for sidecar in self.sidecars().await? {
+            eprintln!("  fwd sidecar {sidecar}");
+        }
+        let override_file = override_file(&self.compose_name);
+        if override_file.exists() {
+            eprintln!("  override file {}", override_file.display());
+        }
+        if let Some(network) = &self.network {
+            eprintln!("  network {network}, if no other workspace uses it");
+        }
+        if self.remove_worktree {
+            let force = if self.force { ", even if dirty" } else { "" };
+            eprintln!("  worktree {}{force}", self.path.display());
+        }
+        if self.trash {
+            eprintln!("  (kept in the trash, to restore with `dc trash restore`)");
+        }
+        if let Some(branch) = &self.branch {
+            eprintln!("  branch {}", branch.name);
+            if let Some((remote, remote_branch)) = &branch.upstream {
+                eprintln!("  branch {remote_branch} of {remote}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn compose_down(&self, volumes: bool) -> eyre::Result<()> {
+        let mut args = self.docker.engine.compose_command();
+        args.extend(["-p", &self.compose_name, "down"].map(ToString::to_string));
+        if volumes {
+            args.push("-v".into());
+        }
+        args.push("--remove-orphans".into());
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        run_cmd(&args, None).await
+    }
+
+    fn sidecar_labels(&self) -> Vec<String> {
+        vec![format!("dev.dc.workspace={}", self.compose_name)]
+    }
+
+    /// The names of port-forward sidecars targeting this workspace.
+    async fn sidecars(&self) -> eyre::Result<Vec<String>> {
+        let mut names = container_names(&self.docker.docker, self.sidecar_labels()).await?;
+        if has_tunnel(&self.compose_name) {
+            names.push("ssh tunnel".into());
+        }
+        Ok(names)
+    }
 }
 
-impl Runnable for Cleanup<'_> {
+/// Everything but the git steps, which `GitCleanup` does.
+impl Runnable for &Cleanup<'_> {
     fn name(&self) -> Cow<'_, str> {
         self.path
             .file_name()
@@ -77,74 +340,107 @@ impl Runnable for Cleanup<'_> {
     }
 
     async fn run(self, _: run::Token) -> eyre::Result<()> {
-        run_cmd(
-            &[
-                "docker",
-                "compose",
-                "-p",
+        if self.trash {
+            // Remove the containers first, so the volumes we keep are consistent.
+            self.compose_down(false).await?;
+            let entry = trash::Entry::put(
+                self.docker,
+                self.project,
+                self.repo_path,
+                self.path,
                 &self.compose_name,
-                "down",
-                "-v",
-                "--remove-orphans",
-            ],
-            None,
-        )
-        .await?;
-
-        let override_file =
-            std::env::temp_dir().join(format!("{}-override.yml", self.compose_name));
+                self.remove_worktree,
+            )
+            .await?;
+            eprintln!("Kept in the trash as {}", entry.name);
+        }
+        self.compose_down(true).await?;
+
+        let override_file = override_file(&self.compose_name);
         if override_file.exists() {
             std::fs::remove_file(&override_file)
                 .wrap_err_with(|| format!("failed to remove {}", override_file.display()))?;
         }
 
         // Remove any port-forward sidecar targeting this workspace
-        let mut filters = HashMap::new();
-        filters.insert(
-            "label".into(),
-            vec![format!("dev.dc.workspace={}", self.compose_name)],
-        );
-        if let Ok(containers) = self
-            .docker
-            .list_containers(Some(ListContainersOptions {
-                all: true,
-                filters: Some(filters),
-                ..Default::default()
-            }))
-            .await
+        let _ = remove_containers(&self.docker.docker, self.sidecar_labels()).await;
+        if let Some(destination) = self.docker.engine.ssh_destination() {
+            close_tunnel(&destination, &self.compose_name);
+        }
+
+        if let Some(network) = &self.network
+            && let Err(e) = self.docker.remove_unused_network(network).await
         {
-            for c in containers {
-                if let Some(id) = c.id {
-                    let _ = self
-                        .docker
-                        .remove_container(
-                            &id,
-                            Some(RemoveContainerOptions {
-                                force: true,
-                                ..Default::default()
-                            }),
-                        )
-                        .await;
-                }
-            }
+            eprintln!("{YELLOW}{e:#}{RESET}");
         }
+        Ok(())
+    }
+}
 
-        if self.remove_worktree {
+/// The rest of a cleanup: removing the worktree and deleting the branch, which write git's config.
+struct GitCleanup<'a>(&'a Cleanup<'a>);
+
+impl Runnable for GitCleanup<'_> {
+    fn name(&self) -> Cow<'_, str> {
+        Runnable::name(&self.0)
+    }
+
+    fn description(&self) -> Cow<'_, str> {
+        format!("remove {} from git", self.0.path.display()).into()
+    }
+
+    async fn run(self, _: run::Token) -> eyre::Result<()> {
+        let cleanup = self.0;
+        if cleanup.remove_worktree {
             let mut args = vec!["git", "worktree", "remove"];
-            if self.force {
+            if cleanup.force {
+                args.push("--force");
+            }
+            if cleanup.force && cleanup.locked {
                 args.push("--force");
             }
-            let path_str = self.path.to_string_lossy();
+            let path_str = cleanup.path.to_string_lossy();
             args.push(&path_str);
 
-            run_cmd(&args, Some(self.repo_path)).await?;
+            run_cmd(&args, Some(cleanup.repo_path)).await?;
+            if let Some(dir) = cleanup.path.file_name() {
+                worktree::set_name(cleanup.repo_path, &dir.to_string_lossy(), None).await?;
+            }
         }
 
-        eprintln!("Removed {}", self.path.display());
+        if let Some(branch) = &cleanup.branch {
+            // We've checked that it's merged into the base branch, which `git branch -d` doesn't
+            // check against, or we're forcing.
+            run_cmd(
+                &["git", "branch", "-D", &branch.name],
+                Some(cleanup.repo_path),
+            )
+            .await?;
+            if let Some((remote, remote_branch)) = &branch.upstream {
+                let args = ["git", "push", "--delete", remote, remote_branch];
+                run_cmd(&args, Some(cleanup.repo_path)).await?;
+            }
+            eprintln!("Deleted branch {}", branch.name);
+        }
+
+        eprintln!("Removed {}", cleanup.path.display());
         Ok(())
     }
 }
 
+/// Run the cleanups, taking down their containers in parallel, then removing their worktrees and
+/// branches one at a time, since git fails rather than wait when another git has its config locked.
+pub(super) async fn run_cleanups(name: &str, cleanups: &[Cleanup<'_>]) -> eyre::Result<()> {
+    match cleanups {
+        [cleanup] => Runner::run(cleanup).await?,
+        cleanups => Runner::run_parallel(name, cleanups).await?,
+    }
+    for cleanup in cleanups {
+        Runner::run(GitCleanup(cleanup)).await?;
+    }
+    Ok(())
+}
+
 pub(super) fn confirm() -> eyre::Result<bool> {
     eprint!("Proceed? [y/N] ");
     std::io::stderr().flush()?;
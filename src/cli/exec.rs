@@ -6,6 +6,7 @@ use clap::Args;
 use eyre::eyre;
 
 use crate::cli::State;
+use crate::engine::Engine;
 use crate::run::cmd::Cmd;
 use crate::workspace::Workspace;
 
@@ -21,18 +22,24 @@ impl Exec {
     pub async fn run(self, state: State) -> eyre::Result<()> {
         let name = state.resolve_workspace().await?;
         let ws = Workspace::get(&state, &name).await?;
-        if ws.status() != ContainerSummaryStateEnum::RUNNING {
-            return Err(eyre!("workspace is not running: {}", ws.path.display()));
-        }
         let dc = state.devcontainer()?;
         let dc_options = dc.common.customizations.dc;
         let crate::devcontainer::Kind::Compose(ref compose) = dc.kind else {
             // This was handled at deserialize time already.
             unimplemented!();
         };
-        let cid = ws.service_container_id()?;
+        let container = ws.service_container(&compose.service)?;
+        if container.state != ContainerSummaryStateEnum::RUNNING {
+            return Err(eyre!("workspace is not running: {}", ws.path.display()));
+        }
+        let cid = &container.id;
+
+        // Close our connection, and any ssh tunnel, as exec will replace this process.
+        let engine = state.docker.engine.clone();
+        drop(state);
 
         exec_interactive(
+            &engine,
             cid,
             dc.common.remote_user.as_deref(),
             Some(compose.workspace_folder.as_path()),
@@ -43,6 +50,7 @@ impl Exec {
 }
 
 pub fn exec_interactive(
+    engine: &Engine,
     container_id: &str,
     user: Option<&str>,
     workdir: Option<&Path>,
@@ -74,7 +82,9 @@ pub fn exec_interactive(
     // replaces the process before indicatif's cleanup can run.
     let _ = crossterm::execute!(std::io::stderr(), crossterm::cursor::Show);
 
-    Err(std::process::Command::new("docker")
+    let cli = engine.cli();
+    Err(std::process::Command::new(&cli[0])
+        .args(&cli[1..])
         .args(&args)
         .exec()
         .into())
@@ -1,7 +1,9 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
 
 use bollard::Docker;
-use bollard::models::{ContainerCreateBody, HostConfig, PortBinding};
+use bollard::models::{ContainerCreateBody, ContainerSummary, HostConfig, PortBinding};
 use bollard::query_parameters::{
     CreateContainerOptions, CreateImageOptionsBuilder, ListContainersOptions,
     RemoveContainerOptions,
@@ -9,9 +11,11 @@ use bollard::query_parameters::{
 use clap::Args;
 use eyre::{WrapErr, eyre};
 use futures::StreamExt;
+use itertools::Itertools;
 
 use crate::cli::State;
 use crate::devcontainer::forward_port::ForwardPort;
+use crate::engine::runtime_dir;
 use crate::workspace::Workspace;
 
 const SOCAT_IMAGE: &str = "docker.io/alpine/socat:latest";
@@ -29,9 +33,11 @@ impl Fwd {
 
 pub async fn forward(state: &State, name: &str) -> eyre::Result<()> {
     let ws = Workspace::get(state, name).await?;
-    let cid = ws.service_container_id()?;
-
     let dc = state.devcontainer()?;
+    let crate::devcontainer::Kind::Compose(ref compose) = dc.kind else {
+        unimplemented!();
+    };
+    let cid = ws.service_container_id(&compose.service)?;
 
     let ports = dc.common.forward_ports;
 
@@ -64,36 +70,102 @@ pub async fn forward(state: &State, name: &str) -> eyre::Result<()> {
 
     ensure_image(&state.docker.docker).await?;
 
+    let labels = sidecar_labels(state, &ws.compose_project_name);
     for port in &ports {
-        create_sidecar(state, &ws.compose_project_name, &network_name, &ip, port).await?;
+        let sidecar = Sidecar {
+            name: sidecar_name(&ws.compose_project_name, port),
+            labels: &labels,
+            network: &network_name,
+            port: port.port,
+            target: port.service.as_deref().unwrap_or(&ip),
+        };
+        sidecar.start(&state.docker.docker).await?;
     }
 
+    // With a remote engine, the sidecars' ports are only on the remote host's loopback, so tunnel
+    // them here.
+    if let Some(destination) = state.docker.engine.ssh_destination() {
+        let ports: Vec<u16> = ports.iter().map(|p| p.port).collect();
+        open_tunnel(&destination, &ws.compose_project_name, &ports)?;
+    }
+
+    for port in &ports {
+        eprintln!("Forwarding to {port}");
+    }
+
+    Ok(())
+}
+
+/// The ssh control socket for the tunnel forwarding a workspace's ports from a remote engine.
+fn tunnel_socket(compose_project_name: &str) -> PathBuf {
+    runtime_dir().join(format!("dc-fwd-{compose_project_name}.ctl"))
+}
+
+/// Forward `ports` on this machine's loopback to the same ports on `destination`'s, with an ssh
+/// process that outlives us, until `close_tunnel`.
+fn open_tunnel(destination: &str, compose_project_name: &str, ports: &[u16]) -> eyre::Result<()> {
+    close_tunnel(destination, compose_project_name);
+    if ports.is_empty() {
+        return Ok(());
+    }
+    let socket = tunnel_socket(compose_project_name);
+    let mut cmd = std::process::Command::new("ssh");
+    cmd.args([
+        "-fNT",
+        "-o",
+        "ExitOnForwardFailure=yes",
+        "-o",
+        "ControlMaster=yes",
+    ])
+    .arg("-o")
+    .arg(format!("ControlPath={}", socket.display()));
+    for port in ports {
+        cmd.arg("-L")
+            .arg(format!("127.0.0.1:{port}:127.0.0.1:{port}"));
+    }
+    // `-f` leaves ssh running with our stdout, which would hold open any pipe reading it.
+    let status = cmd
+        .arg(destination)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status()
+        .wrap_err("failed to spawn ssh")?;
+    eyre::ensure!(
+        status.success(),
+        "ssh tunnel to {destination} exited with {status}"
+    );
     Ok(())
 }
 
-async fn create_sidecar(
-    state: &State,
-    compose_project_name: &str,
-    network_name: &str,
-    ip: &str,
-    fwd_port: &ForwardPort,
-) -> eyre::Result<()> {
+/// Stop the tunnel forwarding a workspace's ports, if there is one.
+pub(crate) fn close_tunnel(destination: &str, compose_project_name: &str) {
+    let socket = tunnel_socket(compose_project_name);
+    if !socket.exists() {
+        return;
+    }
+    let _ = std::process::Command::new("ssh")
+        .arg("-o")
+        .arg(format!("ControlPath={}", socket.display()))
+        .args(["-O", "exit", destination])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Whether a workspace's ports are tunneled from a remote engine.
+pub(crate) fn has_tunnel(compose_project_name: &str) -> bool {
+    tunnel_socket(compose_project_name).exists()
+}
+
+fn sidecar_name(compose_project_name: &str, fwd_port: &ForwardPort) -> String {
     let name = match &fwd_port.service {
         Some(host) => format!("{host}_{}", fwd_port.port),
         None => format!("{}", fwd_port.port),
     };
-    let sidecar_name = format!("dc-fwd-{compose_project_name}-{name}");
-    let port_key = format!("{}/tcp", fwd_port.port);
-
-    let mut port_bindings: HashMap<String, Option<Vec<PortBinding>>> = HashMap::new();
-    port_bindings.insert(
-        port_key.clone(),
-        Some(vec![PortBinding {
-            host_ip: Some("127.0.0.1".to_string()),
-            host_port: Some(fwd_port.port.to_string()),
-        }]),
-    );
+    format!("dc-fwd-{compose_project_name}-{name}")
+}
 
+fn sidecar_labels(state: &State, compose_project_name: &str) -> HashMap<String, String> {
     let mut labels = HashMap::new();
     labels.insert("dev.dc.fwd".to_string(), "true".to_string());
     labels.insert("dev.dc.project".to_string(), state.project_name.clone());
@@ -101,46 +173,59 @@ async fn create_sidecar(
         "dev.dc.workspace".to_string(),
         compose_project_name.to_string(),
     );
+    labels
+}
 
-    state
-        .docker
-        .docker
-        .create_container(
-            Some(CreateContainerOptions {
-                name: Some(sidecar_name.clone()),
-                ..Default::default()
-            }),
-            ContainerCreateBody {
-                image: Some(SOCAT_IMAGE.to_string()),
-                cmd: Some(vec![
-                    format!("TCP-LISTEN:{},fork,reuseaddr", fwd_port.port),
-                    format!(
-                        "TCP:{}:{}",
-                        fwd_port.service.as_deref().unwrap_or(ip),
-                        fwd_port.port
-                    ),
-                ]),
-                labels: Some(labels),
-                exposed_ports: Some(vec![port_key.clone()]),
-                host_config: Some(HostConfig {
-                    network_mode: Some(network_name.to_string()),
-                    port_bindings: Some(port_bindings),
-                    ..Default::default()
-                }),
-                ..Default::default()
-            },
-        )
-        .await?;
+/// A socat container that listens on `127.0.0.1:port` on its engine's host, and forwards to
+/// `target:port` on `network`.
+struct Sidecar<'a> {
+    name: String,
+    labels: &'a HashMap<String, String>,
+    network: &'a str,
+    port: u16,
+    target: &'a str,
+}
 
-    state
-        .docker
-        .docker
-        .start_container(&sidecar_name, None)
-        .await?;
+impl Sidecar<'_> {
+    async fn start(&self, docker: &Docker) -> eyre::Result<()> {
+        let port_key = format!("{}/tcp", self.port);
 
-    eprintln!("Forwarding to {fwd_port}");
+        let mut port_bindings: HashMap<String, Option<Vec<PortBinding>>> = HashMap::new();
+        port_bindings.insert(
+            port_key.clone(),
+            Some(vec![PortBinding {
+                host_ip: Some("127.0.0.1".to_string()),
+                host_port: Some(self.port.to_string()),
+            }]),
+        );
 
-    Ok(())
+        docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: Some(self.name.clone()),
+                    ..Default::default()
+                }),
+                ContainerCreateBody {
+                    image: Some(SOCAT_IMAGE.to_string()),
+                    cmd: Some(vec![
+                        format!("TCP-LISTEN:{},fork,reuseaddr", self.port),
+                        format!("TCP:{}:{}", self.target, self.port),
+                    ]),
+                    labels: Some(self.labels.clone()),
+                    exposed_ports: Some(vec![port_key.clone()]),
+                    host_config: Some(HostConfig {
+                        network_mode: Some(self.network.to_string()),
+                        port_bindings: Some(port_bindings),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        docker.start_container(&self.name, None).await?;
+        Ok(())
+    }
 }
 
 async fn ensure_image(docker: &Docker) -> eyre::Result<()> {
@@ -163,31 +248,28 @@ async fn ensure_image(docker: &Docker) -> eyre::Result<()> {
 }
 
 async fn remove_sidecars(state: &State) -> eyre::Result<()> {
-    let project = &state.project_name;
-    let mut filters = HashMap::new();
-    filters.insert(
-        "label".into(),
-        vec![
-            "dev.dc.fwd=true".to_string(),
-            format!("dev.dc.project={project}"),
-        ],
-    );
-
-    let containers = state
-        .docker
-        .docker
-        .list_containers(Some(ListContainersOptions {
-            all: true,
-            filters: Some(filters),
-            ..Default::default()
-        }))
-        .await?;
+    let labels = vec![
+        "dev.dc.fwd=true".to_string(),
+        format!("dev.dc.project={}", state.project_name),
+    ];
+    if let Some(destination) = state.docker.engine.ssh_destination() {
+        let sidecars = list_containers(&state.docker.docker, labels.clone()).await?;
+        let workspaces = sidecars
+            .into_iter()
+            .filter_map(|c| c.labels?.remove("dev.dc.workspace"))
+            .unique();
+        for workspace in workspaces {
+            close_tunnel(&destination, &workspace);
+        }
+    }
+    remove_containers(&state.docker.docker, labels).await
+}
 
-    for c in containers {
+/// Force-remove all containers, running or not, matching all of the given label filters.
+pub(crate) async fn remove_containers(docker: &Docker, labels: Vec<String>) -> eyre::Result<()> {
+    for c in list_containers(docker, labels).await? {
         if let Some(id) = c.id {
-            let _ = state
-                .docker
-                .docker
+            let _ = docker
                 .remove_container(
                     &id,
                     Some(RemoveContainerOptions {
@@ -200,3 +282,33 @@ async fn remove_sidecars(state: &State) -> eyre::Result<()> {
     }
     Ok(())
 }
+
+/// The names of all containers, running or not, matching all of the given label filters.
+pub(crate) async fn container_names(
+    docker: &Docker,
+    labels: Vec<String>,
+) -> eyre::Result<Vec<String>> {
+    Ok(list_containers(docker, labels)
+        .await?
+        .into_iter()
+        .flat_map(|c| c.names.unwrap_or_default())
+        .map(|n| n.trim_start_matches('/').to_string())
+        .collect())
+}
+
+async fn list_containers(
+    docker: &Docker,
+    labels: Vec<String>,
+) -> eyre::Result<Vec<ContainerSummary>> {
+    let mut filters = HashMap::new();
+    filters.insert("label".into(), labels);
+
+    let containers = docker
+        .list_containers(Some(ListContainersOptions {
+            all: true,
+            filters: Some(filters),
+            ..Default::default()
+        }))
+        .await?;
+    Ok(containers)
+}
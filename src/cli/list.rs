@@ -1,18 +1,167 @@
+use std::time::Duration;
+
 use clap::Args;
+use futures::future::join_all;
+use indexmap::IndexMap;
 
 use crate::{
     cli::State,
-    workspace::{Workspace, table::workspace_table},
+    config::Config,
+    docker::DockerClient,
+    workspace::{
+        Workspace,
+        query::Query,
+        record::{Format, write_records},
+        table::{Column, projects_table, workspace_table},
+    },
 };
 
+mod watch;
+
 /// List all workspaces for the project
 #[derive(Debug, Args)]
-pub struct List;
+pub struct List {
+    /// Output format; all but `table` are written to stdout
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+
+    /// List workspaces of every configured project, and of dc-managed containers whose project is
+    /// no longer configured
+    #[arg(short, long)]
+    pub all_projects: bool,
+
+    /// Include CPU, block and network IO, container disk, and volume usage; slower to collect
+    #[arg(short, long)]
+    stats: bool,
+
+    /// Table columns to show, in order, e.g. `name,status,git` [default: all, with the --stats
+    /// columns only with --stats]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    columns: Vec<Column>,
+
+    #[command(flatten)]
+    query: Query,
+
+    /// Keep running, updating the table as workspaces change
+    #[arg(long, conflicts_with = "all_projects")]
+    watch: bool,
+
+    /// How often, in seconds, to refresh resource use and check for git changes with --watch
+    #[arg(long, default_value_t = 2, requires = "watch")]
+    interval: u64,
+}
 
 impl List {
+    /// Whether to collect detailed stats, either because they were asked for, or a column needs
+    /// them.
+    fn detailed(&self) -> bool {
+        self.stats || self.columns.iter().any(|c| c.is_detailed())
+    }
+
+    fn columns(&self) -> &[Column] {
+        if self.columns.is_empty() {
+            Column::defaults(self.stats)
+        } else {
+            &self.columns
+        }
+    }
+
     pub async fn run(self, state: State) -> eyre::Result<()> {
-        let workspaces = Workspace::list(&state).await?;
-        eprint!("{}", workspace_table(&workspaces));
+        if self.watch {
+            eyre::ensure!(
+                self.format == Format::Table,
+                "--watch only supports the table format"
+            );
+            let interval = Duration::from_secs(self.interval.max(1));
+            let view = watch::View {
+                detailed: self.detailed(),
+                query: &self.query,
+                columns: self.columns(),
+            };
+            return watch::watch(&state, view, interval).await;
+        }
+
+        let workspaces = if self.detailed() {
+            Workspace::list_detailed(&state).await?
+        } else {
+            Workspace::list(&state).await?
+        };
+        match self.format {
+            Format::Table => eprint!(
+                "{}",
+                workspace_table(&workspaces, &self.query, self.columns())
+            ),
+            format => {
+                let records = self
+                    .query
+                    .apply(&workspaces)
+                    .into_iter()
+                    .map(|ws| (&*state.project_name, ws));
+                write_records(&mut std::io::stdout().lock(), format, records)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn run_all(&self, config: Config) -> eyre::Result<()> {
+        let engine = config.engine;
+        let names: Vec<String> = config.projects.keys().cloned().collect();
+        let detailed = self.detailed();
+
+        let futures = config.projects.into_iter().map(async |(name, project)| {
+            let state = State::new(engine.clone(), name.clone(), project, None).await?;
+            let workspaces = if detailed {
+                Workspace::list_detailed(&state).await?
+            } else {
+                Workspace::list(&state).await?
+            };
+            eyre::Ok(workspaces)
+        });
+        let (results, unconfigured) = tokio::join!(join_all(futures), async {
+            let docker = DockerClient::new(engine.clone()).await?;
+            Workspace::unconfigured(&docker, &names, detailed).await
+        });
+
+        let mut projects = IndexMap::new();
+        for (name, result) in names.iter().zip(results) {
+            match result {
+                Ok(workspaces) => {
+                    projects.insert(name.as_str(), workspaces);
+                }
+                Err(e) => tracing::warn!("failed to list project {name}: {e:#}"),
+            }
+        }
+        let unconfigured = unconfigured.unwrap_or_else(|e| {
+            tracing::warn!("failed to list unconfigured projects: {e:#}");
+            IndexMap::new()
+        });
+
+        match self.format {
+            Format::Table => {
+                let sections = projects.iter().map(|(p, ws)| (*p, ws.as_slice()));
+                eprint!("{}", projects_table(sections, &self.query, self.columns()));
+                if !unconfigured.is_empty() {
+                    eprintln!("\nUnconfigured projects:");
+                    let sections = unconfigured
+                        .iter()
+                        .map(|(p, ws)| (p.as_str(), ws.as_slice()));
+                    eprint!("{}", projects_table(sections, &self.query, self.columns()));
+                }
+            }
+            format => {
+                let records = projects
+                    .iter()
+                    .map(|(p, ws)| (*p, ws))
+                    .chain(unconfigured.iter().map(|(p, ws)| (p.as_str(), ws)))
+                    .flat_map(|(project, workspaces)| {
+                        self.query
+                            .apply(workspaces)
+                            .into_iter()
+                            .map(move |ws| (project, ws))
+                    });
+                write_records(&mut std::io::stdout().lock(), format, records)?;
+            }
+        }
         Ok(())
     }
 }
@@ -0,0 +1,92 @@
+//! `dc list --watch`: a live view of the workspace table.
+
+use std::io::{self, Write};
+use std::pin::pin;
+use std::time::Duration;
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    execute, queue,
+    style::Print,
+    terminal::{Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use futures::StreamExt;
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    cli::State,
+    workspace::{
+        live::{LiveWorkspaces, settle},
+        query::Query,
+        table::{Column, workspace_table},
+    },
+};
+
+/// What to show, from `dc list`'s arguments.
+pub struct View<'a> {
+    pub detailed: bool,
+    pub query: &'a Query,
+    pub columns: &'a [Column],
+}
+
+pub async fn watch(state: &State, view: View<'_>, interval: Duration) -> eyre::Result<()> {
+    let mut events = pin!(state.docker.container_events());
+    let mut ctrl_c = pin!(tokio::signal::ctrl_c());
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // The first tick is immediate, and we've nothing to refresh yet.
+    ticker.tick().await;
+
+    let mut live = LiveWorkspaces::new(state, view.detailed).await?;
+    let mut screen = Screen::enter()?;
+    loop {
+        let now = jiff::Zoned::now().strftime("%H:%M:%S");
+        let title = format!(
+            "Workspaces of {} at {now}. Ctrl-C to quit.\n\n",
+            state.project_name
+        );
+        let table = workspace_table(&live.workspaces, view.query, view.columns);
+        screen.render(&(title + &table.to_string()))?;
+
+        tokio::select! {
+            result = &mut ctrl_c => return Ok(result?),
+            event = events.next() => {
+                settle(event, &mut events).await?;
+                live.relist(state).await?;
+            }
+            _ = ticker.tick() => live.refresh(state).await?,
+        }
+    }
+}
+
+/// The terminal's alternate screen, which we draw on until dropped.
+struct Screen;
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        execute!(io::stderr(), EnterAlternateScreen, Hide)?;
+        Ok(Screen)
+    }
+
+    /// Replace what's on screen with `content`, line by line, to avoid flicker.
+    fn render(&mut self, content: &str) -> io::Result<()> {
+        let mut out = io::stderr().lock();
+        queue!(out, MoveTo(0, 0))?;
+        for line in content.lines() {
+            queue!(
+                out,
+                Print(line),
+                Clear(ClearType::UntilNewLine),
+                Print("\r\n")
+            )?;
+        }
+        queue!(out, Clear(ClearType::FromCursorDown))?;
+        out.flush()
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stderr(), Show, LeaveAlternateScreen);
+    }
+}
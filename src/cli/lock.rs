@@ -0,0 +1,58 @@
+use clap::Args;
+use eyre::eyre;
+
+use crate::cli::State;
+use crate::worktree;
+
+/// Protect the workspace from `dc destroy` and `dc prune`, unless given `--force`
+#[derive(Debug, Args)]
+pub struct Lock {
+    /// Why the workspace is locked, shown by `dc list --format json`
+    #[arg(short, long)]
+    reason: Option<String>,
+}
+
+/// Stop protecting the workspace from `dc destroy` and `dc prune`
+#[derive(Debug, Args)]
+pub struct Unlock;
+
+impl Lock {
+    pub async fn run(self, state: State) -> eyre::Result<()> {
+        let (name, wt) = find_worktree(&state).await?;
+        if wt.locked.is_some() {
+            return Err(eyre!("workspace {name} is already locked"));
+        }
+        worktree::lock(&state.project.path, &wt.path, self.reason.as_deref()).await?;
+        eprintln!("Locked {name}");
+        Ok(())
+    }
+}
+
+impl Unlock {
+    pub async fn run(self, state: State) -> eyre::Result<()> {
+        let (name, wt) = find_worktree(&state).await?;
+        if wt.locked.is_none() {
+            return Err(eyre!("workspace {name} is not locked"));
+        }
+        worktree::unlock(&state.project.path, &wt.path).await?;
+        eprintln!("Unlocked {name}");
+        Ok(())
+    }
+}
+
+/// The name and worktree of the workspace to lock or unlock.
+async fn find_worktree(state: &State) -> eyre::Result<(String, worktree::Worktree)> {
+    let name = state.resolve_workspace().await?;
+    let wt = worktree::list(&state.project.path)
+        .await?
+        .into_iter()
+        .find(|wt| {
+            if state.is_root(&name) {
+                wt.path == state.project.path
+            } else {
+                wt.path.file_name().is_some_and(|f| f == name.as_str())
+            }
+        })
+        .ok_or_else(|| eyre!("no worktree for workspace {name}"))?;
+    Ok((name, wt))
+}
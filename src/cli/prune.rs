@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use clap::{ArgGroup, Args};
+use futures::future::try_join_all;
+use jiff::{SignedDuration, Timestamp};
+
+use crate::cli::State;
+use crate::cli::destroy::{Cleanup, confirm, run_cleanups};
+use crate::docker::DockerClient;
+use crate::workspace::Workspace;
+use crate::workspace::git_status::Merged;
+use crate::workspace::query::Query;
+use crate::workspace::table::{Column, workspace_table};
+
+/// Destroy workspaces that look finished, e.g. merged or long stopped. Never the root workspace, nor
+/// locked ones.
+///
+/// Workspaces must match every criterion given. Without `--force`, workspaces with uncommitted
+/// changes, or whose branch has no commits of its own, are never pruned.
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("criteria").required(true).multiple(true)))]
+pub struct Prune {
+    /// Workspaces whose branch is merged into the base branch, including by squashing
+    #[arg(long, group = "criteria")]
+    merged: bool,
+
+    /// Workspaces without uncommitted changes
+    #[arg(long, group = "criteria")]
+    clean: bool,
+
+    /// Workspaces whose containers have all been stopped for at least this many days, or that
+    /// have had none for that long
+    #[arg(long, value_name = "DAYS", group = "criteria")]
+    stopped: Option<u32>,
+
+    /// Workspaces whose worktree was created at least this many days ago
+    #[arg(long, value_name = "DAYS", group = "criteria")]
+    older_than: Option<u32>,
+
+    /// Show what would be destroyed, without destroying anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Don't ask for confirmation
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Also prune workspaces with uncommitted changes, or no commits of their own, and force
+    /// remove their worktrees
+    #[arg(short, long)]
+    force: bool,
+}
+
+impl Prune {
+    pub async fn run(self, state: State) -> eyre::Result<()> {
+        let workspaces = Workspace::list(&state).await?;
+        let candidates = workspaces
+            .into_iter()
+            .filter(|ws| !ws.root && !ws.is_locked() && ws.path.exists());
+        let futures = candidates.map(async |ws| {
+            let selected = self.selects(&state.docker, &ws).await?;
+            eyre::Ok(selected.then_some(ws))
+        });
+        let selected: Vec<Workspace> = try_join_all(futures).await?.into_iter().flatten().collect();
+
+        if selected.is_empty() {
+            eprintln!("Nothing to prune.");
+            return Ok(());
+        }
+
+        eprint!(
+            "{}",
+            workspace_table(&selected, &Query::default(), Column::defaults(false))
+        );
+        if self.dry_run {
+            return Ok(());
+        }
+        if !self.yes && !confirm()? {
+            eprintln!("Aborted.");
+            return Ok(());
+        }
+
+        let network = state
+            .devcontainer()?
+            .common
+            .customizations
+            .dc
+            .project_network(&state.project_name);
+        let cleanups: Vec<_> = selected
+            .iter()
+            .map(|ws| Cleanup::new(&state, &ws.path, self.force, network.clone()))
+            .collect();
+        run_cleanups("prune", &cleanups).await
+    }
+
+    async fn selects(&self, docker: &DockerClient, ws: &Workspace) -> eyre::Result<bool> {
+        // Volumes are removed before the worktree, which git refuses to remove if it's dirty, so
+        // don't start on one. Nor on a new workspace, which has nothing to be finished with.
+        if !self.force && (ws.is_dirty() || ws.git_status.merged == Some(Merged::NoCommits)) {
+            return Ok(false);
+        }
+        if self.merged && !matches!(ws.git_status.merged, Some(Merged::Yes | Merged::Squashed)) {
+            return Ok(false);
+        }
+        if self.clean && ws.is_dirty() {
+            return Ok(false);
+        }
+        if let Some(days) = self.older_than
+            && !is_older(created(&ws.path), days)
+        {
+            return Ok(false);
+        }
+        if let Some(days) = self.stopped
+            && !is_older(stopped_since(docker, ws).await?, days)
+        {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+/// Whether `time` was at least `days` ago.
+fn is_older(time: Option<Timestamp>, days: u32) -> bool {
+    time.is_some_and(|t| {
+        Timestamp::now().duration_since(t) >= SignedDuration::from_hours(24 * i64::from(days))
+    })
+}
+
+/// When the worktree at `path` was created, going by its `.git` file, which git writes then.
+fn created(path: &Path) -> Option<Timestamp> {
+    let modified = path.join(".git").symlink_metadata().ok()?.modified().ok()?;
+    Timestamp::try_from(modified).ok()
+}
+
+/// When the last of the workspace's containers stopped, or when its worktree was created if it has
+/// none. `None` if any are running.
+async fn stopped_since(docker: &DockerClient, ws: &Workspace) -> eyre::Result<Option<Timestamp>> {
+    if ws.containers.is_empty() {
+        return Ok(created(&ws.path));
+    }
+    if ws.is_running() {
+        return Ok(None);
+    }
+    let finished = try_join_all(ws.containers.iter().map(|c| docker.finished_at(&c.id))).await?;
+    // A container that was created, but never run, counts from its creation.
+    let created = ws.created().and_then(|c| Timestamp::from_second(c).ok());
+    Ok(finished.into_iter().flatten().chain(created).max())
+}
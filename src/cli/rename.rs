@@ -0,0 +1,276 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use bollard::query_parameters::RemoveVolumeOptions;
+use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
+use eyre::{WrapErr, eyre};
+use futures::future::try_join_all;
+use itertools::Itertools;
+
+use crate::ansi::{RESET, YELLOW};
+use crate::cli::State;
+use crate::cli::copy::do_copy_volume;
+use crate::cli::fwd::{close_tunnel, remove_containers};
+use crate::cli::up::{ComposeExtras, Up, compose_project_name, override_file};
+use crate::complete;
+use crate::docker::DockerClient;
+use crate::run::{self, Runnable, Runner, run_cmd};
+use crate::workspace::Workspace;
+use crate::worktree;
+
+/// Rename a workspace, moving its worktree and volumes, and recreating its containers if it was
+/// running
+#[derive(Debug, Args)]
+pub struct Rename {
+    /// The workspace to rename
+    #[arg(add = ArgValueCompleter::new(complete::complete_workspace))]
+    old: String,
+
+    /// Its new name, which may be branch-style, like `user/thing`
+    new: String,
+
+    /// Rename its branch too, to the one `dc up` would create for the new name
+    #[arg(short, long)]
+    branch: bool,
+
+    /// Rename it even if it's locked
+    #[arg(short, long)]
+    force: bool,
+}
+
+impl Rename {
+    pub async fn run(self, mut state: State) -> eyre::Result<()> {
+        let old = state.resolve(&self.old).await?;
+        let new = worktree::dir_name(&self.new)?;
+        if state.is_root(&old) {
+            return Err(eyre!("the root workspace can't be renamed"));
+        }
+
+        let ws = Workspace::get(&state, &old).await?;
+        if !ws.path.exists() {
+            return Err(eyre!("no workspace named '{old}' found"));
+        }
+        if ws.is_locked() && !self.force {
+            return Err(eyre!(
+                "workspace {old} is locked; unlock it with `dc unlock`, or use --force"
+            ));
+        }
+        let to = ws
+            .path
+            .parent()
+            .ok_or_else(|| eyre!("worktree path has no parent"))?
+            .join(&new);
+        if to.exists() {
+            return Err(eyre!("{} already exists", to.display()));
+        }
+        // Copying into a leftover volume would merge into it, rather than fail.
+        let (old_project, new_project) =
+            (compose_project_name(&ws.path), compose_project_name(&to));
+        if new_project != old_project {
+            let leftovers = state.docker.project_volumes(&new_project).await?;
+            if !leftovers.is_empty() {
+                let names = leftovers.iter().map(|v| v.name.as_str()).join(", ");
+                return Err(eyre!(
+                    "volumes of compose project {new_project} already exist: {names}; remove them first"
+                ));
+            }
+        }
+
+        let dc = state.devcontainer()?;
+        let branch = match (&ws.git_status.branch, self.branch) {
+            (Some(old_branch), true) => {
+                let new_branch = dc.common.customizations.dc.branch_name(&self.new)?;
+                Some((old_branch.clone(), new_branch))
+            }
+            (None, true) => {
+                eprintln!("Workspace {old} has no branch to rename");
+                None
+            }
+            (_, false) => None,
+        };
+
+        // Containers are removed by the move, so note how to bring them back.
+        let extras = ComposeExtras::recorded(&state, &ws.path).await?;
+        let running = ws.is_running();
+        let forwarded = !ws.fwd_ports.is_empty();
+
+        let move_ = Move {
+            docker: &state.docker,
+            repo_path: &state.project.path,
+            from: ws.path.clone(),
+            to,
+            name: self.new.clone(),
+            branch,
+            locked: ws.is_locked(),
+        };
+        let moved = Runner::run(move_).await;
+
+        if running {
+            // A failed move leaves the workspace where it was, so bring it back up there.
+            state.workspace = Some(if moved.is_ok() { new } else { old });
+            let up = Up::again(extras, forwarded).run(state).await;
+            moved?;
+            return up;
+        }
+        moved
+    }
+}
+
+/// Move a workspace's worktree and volumes.
+struct Move<'a> {
+    docker: &'a DockerClient,
+    repo_path: &'a Path,
+    from: PathBuf,
+    to: PathBuf,
+    /// The new name, which may be branch-style.
+    name: String,
+    /// The branch to rename, and its new name.
+    branch: Option<(String, String)>,
+    /// Whether the worktree is locked, which git needs forcing twice to move.
+    locked: bool,
+}
+
+impl Runnable for Move<'_> {
+    fn name(&self) -> Cow<'_, str> {
+        "rename".into()
+    }
+
+    fn description(&self) -> Cow<'_, str> {
+        format!("{} -> {}", self.from.display(), self.to.display()).into()
+    }
+
+    /// Move the workspace, leaving it as it was, besides its containers, if that fails.
+    async fn run(self, _: run::Token) -> eyre::Result<()> {
+        let old_project = compose_project_name(&self.from);
+        let new_project = compose_project_name(&self.to);
+
+        let mut args = self.docker.engine.compose_command();
+        args.extend(["-p", &old_project, "down", "--remove-orphans"].map(ToString::to_string));
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        run_cmd(&args, None).await?;
+
+        let old_override = override_file(&old_project);
+        if old_override.exists() {
+            std::fs::remove_file(&old_override)
+                .wrap_err_with(|| format!("failed to remove {}", old_override.display()))?;
+        }
+        let labels = vec![format!("dev.dc.workspace={old_project}")];
+        let _ = remove_containers(&self.docker.docker, labels).await;
+        if let Some(destination) = self.docker.engine.ssh_destination() {
+            close_tunnel(&destination, &old_project);
+        }
+
+        // Names differing only in characters compose drops share a project, and so volumes.
+        if new_project == old_project {
+            self.move_worktree().await?;
+        } else {
+            // Copy the volumes first, and only then move the worktree, so that if either fails, we
+            // can drop the copies and leave the workspace as it was.
+            let moved = match self.copy_volumes(&old_project, &new_project).await {
+                Ok(()) => self.move_worktree().await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = moved {
+                if let Err(e) = remove_volumes(self.docker, &new_project).await {
+                    eprintln!("{YELLOW}Failed to remove copied volumes: {e:#}{RESET}");
+                }
+                return Err(e);
+            }
+            if let Err(e) = remove_volumes(self.docker, &old_project).await {
+                eprintln!("{YELLOW}Failed to remove old volumes: {e:#}{RESET}");
+            }
+        }
+
+        eprintln!("Moved {} to {}", self.from.display(), self.to.display());
+        Ok(())
+    }
+}
+
+impl Move<'_> {
+    /// Copy the old project's volumes to the new one's, checking that they're all there.
+    async fn copy_volumes(&self, old_project: &str, new_project: &str) -> eyre::Result<()> {
+        let volumes = self.docker.project_volumes(old_project).await?;
+        let copies = volumes.into_iter().map(async |volume| {
+            let key = volume
+                .labels
+                .get("com.docker.compose.volume")
+                .cloned()
+                .or_else(|| {
+                    let prefix = format!("{old_project}_");
+                    volume.name.strip_prefix(&prefix).map(ToString::to_string)
+                })
+                .ok_or_else(|| eyre!("volume {} has no compose volume name", volume.name))?;
+            let mut labels = volume.labels;
+            labels.insert("com.docker.compose.project".into(), new_project.to_string());
+            let new_name = format!("{new_project}_{key}");
+            do_copy_volume(&self.docker.docker, &volume.name, &new_name, labels).await?;
+            eyre::Ok(new_name)
+        });
+        let expected = try_join_all(copies).await?;
+
+        let copied = self.docker.project_volumes(new_project).await?;
+        if let Some(missing) = expected
+            .iter()
+            .find(|name| !copied.iter().any(|v| &v.name == *name))
+        {
+            return Err(eyre!("volume {missing} is missing after copying"));
+        }
+        Ok(())
+    }
+
+    /// Rename the branch, if asked to, and move the worktree, undoing the rename if the move fails.
+    async fn move_worktree(&self) -> eyre::Result<()> {
+        if let Some((old_branch, new_branch)) = &self.branch {
+            let args = ["git", "branch", "-m", old_branch, new_branch];
+            run_cmd(&args, Some(self.repo_path)).await?;
+        }
+
+        let (from, to) = (self.from.to_string_lossy(), self.to.to_string_lossy());
+        let mut args = vec!["git", "worktree", "move"];
+        if self.locked {
+            args.extend(["--force", "--force"]);
+        }
+        args.extend([&*from, &to]);
+        if let Err(e) = run_cmd(&args, Some(self.repo_path)).await {
+            if let Some((old_branch, new_branch)) = &self.branch {
+                let args = ["git", "branch", "-m", new_branch, old_branch];
+                if let Err(e) = run_cmd(&args, Some(self.repo_path)).await {
+                    eprintln!("{YELLOW}Failed to rename branch {new_branch} back: {e:#}{RESET}");
+                }
+            }
+            return Err(e);
+        }
+        if let Some((old_branch, new_branch)) = &self.branch {
+            eprintln!("Renamed branch {old_branch} to {new_branch}");
+        }
+
+        // The worktree has moved, so failing to record its name isn't worth undoing that for.
+        let dir_name = |path: &Path| {
+            let name = path.file_name().unwrap_or_default();
+            name.to_string_lossy().into_owned()
+        };
+        let (old_dir, new_dir) = (dir_name(&self.from), dir_name(&self.to));
+        let name = (self.name != new_dir).then_some(self.name.as_str());
+        let names = async {
+            worktree::set_name(self.repo_path, &old_dir, None).await?;
+            worktree::set_name(self.repo_path, &new_dir, name).await
+        };
+        if let Err(e) = names.await {
+            eprintln!("{YELLOW}Failed to record the workspace's name: {e:#}{RESET}");
+        }
+        Ok(())
+    }
+}
+
+/// Remove all of the compose project's volumes.
+async fn remove_volumes(docker: &DockerClient, project: &str) -> eyre::Result<()> {
+    for volume in docker.project_volumes(project).await? {
+        docker
+            .docker
+            .remove_volume(&volume.name, None::<RemoveVolumeOptions>)
+            .await
+            .wrap_err_with(|| format!("failed to remove volume {}", volume.name))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use bollard::query_parameters::RemoveVolumeOptions;
+use clap::Args;
+use eyre::WrapErr;
+use vec1::Vec1;
+
+use crate::cli::State;
+use crate::cli::destroy::confirm;
+use crate::cli::up::compose_project_name;
+use crate::docker::DockerClient;
+use crate::run::cmd::{Cmd, NamedCmd};
+use crate::run::{Runner, run_cmd};
+use crate::worktree;
+
+/// Recreate or prune worktrees whose directories are gone, e.g. from `/tmp` after a reboot, and
+/// remove containers whose worktree no longer exists
+#[derive(Debug, Args)]
+pub struct Repair {
+    /// Recreate every missing worktree, at the same path, without asking
+    #[arg(long, conflicts_with = "prune")]
+    recreate: bool,
+
+    /// Prune every missing worktree, without asking
+    #[arg(long)]
+    prune: bool,
+
+    /// Show what's broken, without changing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Don't ask for confirmation before removing orphaned containers
+    #[arg(short, long)]
+    yes: bool,
+}
+
+/// What to do about a missing worktree.
+enum Fix {
+    Recreate,
+    Prune,
+    Skip,
+}
+
+impl Repair {
+    pub async fn run(self, state: State) -> eyre::Result<()> {
+        let repo_path = &state.project.path;
+        let worktrees = worktree::list(repo_path).await?;
+        let workspace_dir = state
+            .devcontainer()?
+            .common
+            .customizations
+            .dc
+            .workspace_dir(repo_path);
+        let containers = state.docker.container_info().await?;
+        // Our containers' worktree paths, and how many each has.
+        let mut folders: BTreeMap<PathBuf, usize> = BTreeMap::new();
+        for c in containers {
+            // Containers from before we labeled them are ours if their worktree is, or would be.
+            let ours = match &c.dc_project {
+                Some(project) => project == &state.project_name,
+                None => {
+                    c.local_folder.parent() == Some(workspace_dir.as_path())
+                        || worktrees.iter().any(|wt| wt.path == c.local_folder)
+                }
+            };
+            if ours {
+                *folders.entry(c.local_folder).or_default() += 1;
+            }
+        }
+
+        let missing = worktrees
+            .iter()
+            .filter(|wt| &wt.path != repo_path && !wt.path.exists());
+        let mut found = false;
+        let mut pruned = Vec::new();
+        for wt in missing {
+            found = true;
+            let containers = folders.get(&wt.path).copied().unwrap_or_default();
+            let checkout = wt.branch.as_deref().unwrap_or("detached");
+            eprintln!(
+                "{} is missing ({checkout}, {containers} containers)",
+                wt.path.display()
+            );
+            // Git's locks are for worktrees that are expected to go missing, like on removable
+            // drives.
+            if wt.locked.is_some() {
+                eprintln!("  It's locked, so leaving it alone.");
+                continue;
+            }
+            if self.dry_run {
+                continue;
+            }
+            match self.fix()? {
+                Fix::Recreate => {
+                    worktree::recreate(repo_path, wt).await?;
+                    eprintln!("Recreated {}", wt.path.display());
+                    if containers > 0 {
+                        let name = wt.path.file_name().unwrap_or_default().to_string_lossy();
+                        eprintln!(
+                            "  Its volumes are intact; run `dc -w {name} up` to recreate its containers."
+                        );
+                    }
+                }
+                Fix::Prune => {
+                    let path = wt.path.to_string_lossy();
+                    let args = ["git", "worktree", "remove", "--force", &path];
+                    run_cmd(&args, Some(repo_path)).await?;
+                    if let Some(dir) = wt.path.file_name() {
+                        worktree::set_name(repo_path, &dir.to_string_lossy(), None).await?;
+                    }
+                    eprintln!("Pruned {path}");
+                    // With no containers using them, its volumes can go now. Otherwise, they go
+                    // once its containers are removed, below.
+                    if containers == 0 {
+                        remove_volumes(&state.docker, &wt.path).await?;
+                    }
+                    pruned.push(wt.path.clone());
+                }
+                Fix::Skip => {}
+            }
+        }
+
+        // Containers of worktrees that are still registered, but missing, stay until the worktree
+        // is dealt with.
+        let worktrees = worktree::list(repo_path).await?;
+        let orphans: Vec<(&Path, usize)> = folders
+            .iter()
+            .filter(|(path, _)| !path.exists() && !worktrees.iter().any(|wt| &wt.path == *path))
+            .map(|(path, n)| (path.as_path(), *n))
+            .collect();
+        if !orphans.is_empty() {
+            found = true;
+            eprintln!("These containers' worktrees no longer exist:");
+            for (path, n) in &orphans {
+                let project = compose_project_name(path);
+                eprintln!("  {project} ({n} containers, from {})", path.display());
+                for volume in state.docker.project_volumes(&project).await? {
+                    eprintln!("    volume {}", volume.name);
+                }
+            }
+        }
+
+        if !found {
+            eprintln!("Nothing to repair.");
+            return Ok(());
+        }
+        if self.dry_run || orphans.is_empty() {
+            return Ok(());
+        }
+        if pruned.is_empty() {
+            eprintln!("Their volumes are kept, for if the worktree is recreated.");
+        } else {
+            eprintln!(
+                "Volumes of the worktrees just pruned are removed; the rest are kept, for if the \
+                 worktree is recreated."
+            );
+        }
+        if !self.yes && !confirm()? {
+            eprintln!("Aborted.");
+            return Ok(());
+        }
+
+        let cmds = orphans
+            .iter()
+            .map(|(path, _)| {
+                let mut args = state.docker.engine.compose_command();
+                args.extend(["-p".into(), compose_project_name(path)]);
+                args.extend(["down", "--remove-orphans"].map(ToString::to_string));
+                Ok(Cmd::Args(Vec1::try_from_vec(args)?))
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let cmds = cmds.iter().map(|cmd| NamedCmd {
+            name: "compose down",
+            cmd,
+            dir: None,
+        });
+        Runner::run_parallel("remove orphans", cmds).await?;
+
+        for (path, _) in orphans
+            .iter()
+            .filter(|(path, _)| pruned.iter().any(|p| p == path))
+        {
+            remove_volumes(&state.docker, path).await?;
+        }
+        Ok(())
+    }
+
+    /// What to do about a missing worktree, from the flags, or else by asking.
+    fn fix(&self) -> eyre::Result<Fix> {
+        if self.recreate {
+            return Ok(Fix::Recreate);
+        }
+        if self.prune {
+            return Ok(Fix::Prune);
+        }
+        eprint!("[r]ecreate, [p]rune, or [s]kip? [s] ");
+        std::io::stderr().flush()?;
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        Ok(match line.trim().to_ascii_lowercase().as_str() {
+            "r" | "recreate" => Fix::Recreate,
+            "p" | "prune" => Fix::Prune,
+            _ => Fix::Skip,
+        })
+    }
+}
+
+/// Remove the volumes of the workspace whose worktree was at `path`.
+async fn remove_volumes(docker: &DockerClient, path: &Path) -> eyre::Result<()> {
+    for volume in docker.project_volumes(&compose_project_name(path)).await? {
+        docker
+            .docker
+            .remove_volume(&volume.name, None::<RemoveVolumeOptions>)
+            .await
+            .wrap_err_with(|| format!("failed to remove volume {}", volume.name))?;
+        eprintln!("Removed volume {}", volume.name);
+    }
+    Ok(())
+}
@@ -1,10 +1,16 @@
 use std::path::Path;
 
+use bollard::secret::ContainerSummaryStateEnum;
 use clap::{Args, Subcommand};
+use eyre::eyre;
 use itertools::Itertools;
+use tabular::{Row, Table};
 
-use crate::cli::State;
 use crate::cli::up::compose_project_name;
+use crate::cli::{ExitCode, State};
+use crate::workspace::Workspace;
+use crate::workspace::table::state_cell;
+use crate::worktree;
 
 /// Show some value
 #[derive(Debug, Args)]
@@ -19,6 +25,20 @@ enum ShowCommands {
     Ports(Ports),
     /// Print the current workspace name, or exit 1
     Workspace(ShowWorkspace),
+    /// Show the state of each of the workspace's containers, by compose service
+    Containers(Containers),
+    /// Print the path of the workspace's worktree
+    Path(ShowPath),
+    /// Print the ID of the workspace's primary container, or exit 1
+    Container(Container),
+    /// Print the workspace's compose project name
+    ComposeProject(ComposeProject),
+    /// Print the workspace's state; exit 0 if running, 1 if stopped, or 2 if it has no containers
+    Status(Status),
+    /// Print the branch checked out in the workspace, or exit 1 if its HEAD is detached
+    Branch(Branch),
+    /// Print the environment of processes run in the container, as KEY=value lines
+    Env(Env),
 }
 
 #[derive(Debug, Args)]
@@ -27,11 +47,39 @@ struct Ports;
 #[derive(Debug, Args)]
 struct ShowWorkspace;
 
+#[derive(Debug, Args)]
+struct Containers;
+
+#[derive(Debug, Args)]
+struct ShowPath;
+
+#[derive(Debug, Args)]
+struct Container;
+
+#[derive(Debug, Args)]
+struct ComposeProject;
+
+#[derive(Debug, Args)]
+struct Status;
+
+#[derive(Debug, Args)]
+struct Branch;
+
+#[derive(Debug, Args)]
+struct Env;
+
 impl Show {
     pub async fn run(self, state: State) -> eyre::Result<()> {
         match self.command {
             ShowCommands::Ports(ports) => ports.run(state).await,
             ShowCommands::Workspace(ws) => ws.run(state).await,
+            ShowCommands::Containers(containers) => containers.run(state).await,
+            ShowCommands::Path(path) => path.run(state).await,
+            ShowCommands::Container(container) => container.run(state).await,
+            ShowCommands::ComposeProject(project) => project.run(state).await,
+            ShowCommands::Status(status) => status.run(state).await,
+            ShowCommands::Branch(branch) => branch.run(state).await,
+            ShowCommands::Env(env) => env.run(state),
         }
     }
 }
@@ -58,7 +106,134 @@ impl ShowWorkspace {
                 println!("{name}");
                 Ok(())
             }
-            Err(_) => std::process::exit(1),
+            Err(_) => Err(ExitCode(1).into()),
+        }
+    }
+}
+
+impl Containers {
+    async fn run(self, state: State) -> eyre::Result<()> {
+        let name = state.resolve_workspace().await?;
+        let ws = Workspace::get(&state, &name).await?;
+        let dc = state.devcontainer()?;
+        let crate::devcontainer::Kind::Compose(ref compose) = dc.kind else {
+            unimplemented!();
+        };
+        let primary = ws.service_container_id(&compose.service).ok();
+
+        let mut containers: Vec<_> = ws.containers.iter().collect();
+        containers.sort_by_key(|c| (Some(c.id.as_str()) != primary, c.service.clone()));
+
+        let mut table = Table::new("{:<}  {:<}  {:<}  {:<}  {:<}");
+        table.add_row(
+            Row::new()
+                .with_cell("SERVICE")
+                .with_cell("STATE")
+                .with_cell("PRIMARY")
+                .with_cell("ID")
+                .with_cell("PORTS"),
+        );
+        for c in containers {
+            let is_primary = if Some(c.id.as_str()) == primary {
+                "\u{2713}"
+            } else {
+                ""
+            };
+            table.add_row(
+                Row::new()
+                    .with_cell(c.service.as_deref().unwrap_or("-"))
+                    .with_ansi_cell(state_cell(c.state))
+                    .with_cell(is_primary)
+                    .with_cell(&c.id[..c.id.len().min(12)])
+                    .with_cell(c.host_ports.iter().join(",")),
+            );
         }
+        eprint!("{table}");
+        Ok(())
+    }
+}
+
+impl ShowPath {
+    async fn run(self, state: State) -> eyre::Result<()> {
+        let name = state.resolve_workspace().await?;
+        let dc = state.devcontainer()?;
+        let path = state.worktree_path(&dc, &name);
+        if !path.exists() {
+            return Err(eyre!("no worktree for workspace {name}"));
+        }
+        println!("{}", path.display());
+        Ok(())
+    }
+}
+
+impl Container {
+    async fn run(self, state: State) -> eyre::Result<()> {
+        let name = state.resolve_workspace().await?;
+        let ws = Workspace::get(&state, &name).await?;
+        let dc = state.devcontainer()?;
+        let crate::devcontainer::Kind::Compose(ref compose) = dc.kind else {
+            unimplemented!();
+        };
+        match ws.service_container_id(&compose.service) {
+            Ok(id) => {
+                println!("{id}");
+                Ok(())
+            }
+            Err(_) => Err(ExitCode(1).into()),
+        }
+    }
+}
+
+impl ComposeProject {
+    async fn run(self, state: State) -> eyre::Result<()> {
+        let name = state.resolve_workspace().await?;
+        println!("{}", compose_project_name(Path::new(&name)));
+        Ok(())
+    }
+}
+
+impl Status {
+    async fn run(self, state: State) -> eyre::Result<()> {
+        let name = state.resolve_workspace().await?;
+        let ws = Workspace::get(&state, &name).await?;
+        let status = ws.status();
+        match status {
+            ContainerSummaryStateEnum::EMPTY => {
+                println!("none");
+                Err(ExitCode(2).into())
+            }
+            ContainerSummaryStateEnum::RUNNING => {
+                println!("{status}");
+                Ok(())
+            }
+            _ => {
+                println!("{status}");
+                Err(ExitCode(1).into())
+            }
+        }
+    }
+}
+
+impl Branch {
+    async fn run(self, state: State) -> eyre::Result<()> {
+        let name = state.resolve_workspace().await?;
+        let dc = state.devcontainer()?;
+        match worktree::branch(&state.worktree_path(&dc, &name))? {
+            Some(branch) => {
+                println!("{branch}");
+                Ok(())
+            }
+            None => Err(ExitCode(1).into()),
+        }
+    }
+}
+
+impl Env {
+    fn run(self, state: State) -> eyre::Result<()> {
+        let dc = state.devcontainer()?;
+        for (key, value) in dc.common.env() {
+            println!("{key}={value}");
+        }
+        Ok(())
     }
 }
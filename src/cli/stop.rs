@@ -0,0 +1,35 @@
+use clap::Args;
+use vec1::Vec1;
+
+use crate::cli::State;
+use crate::cli::up::{ComposeExtras, compose_base_args};
+use crate::run::Runner;
+use crate::run::cmd::{Cmd, NamedCmd};
+
+/// Stop the workspace's containers, including those from profiles it was brought up with
+#[derive(Debug, Args)]
+pub struct Stop;
+
+impl Stop {
+    pub async fn run(self, state: State) -> eyre::Result<()> {
+        let name = state.resolve_workspace().await?;
+        let dc = state.devcontainer()?;
+        let crate::devcontainer::Kind::Compose(ref compose) = dc.kind else {
+            unimplemented!();
+        };
+
+        let worktree_path = state.worktree_path(&dc, &name);
+        let extras = ComposeExtras::recorded(&state, &worktree_path).await?;
+
+        let mut args =
+            compose_base_args(&state.docker.engine, compose, &worktree_path, None, &extras);
+        args.push("stop".into());
+
+        let cmd = NamedCmd {
+            name: "compose stop",
+            cmd: &Cmd::Args(Vec1::try_from_vec(args)?),
+            dir: None,
+        };
+        Runner::run(cmd).await
+    }
+}
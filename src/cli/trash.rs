@@ -0,0 +1,97 @@
+use clap::{Args, Subcommand};
+use tabular::{Row, Table};
+
+use crate::cli::State;
+use crate::trash::{self, Entry};
+use crate::workspace::table::format_age;
+
+/// Restore destroyed workspaces, which are kept for the project's trash_days
+#[derive(Debug, Args)]
+pub struct Trash {
+    #[command(subcommand)]
+    command: TrashCommands,
+}
+
+#[derive(Debug, Subcommand)]
+enum TrashCommands {
+    /// List destroyed workspaces that can be restored
+    List(List),
+    /// Recreate a destroyed workspace's worktree, with its uncommitted changes, and its volumes
+    Restore(Restore),
+}
+
+#[derive(Debug, Args)]
+struct List;
+
+#[derive(Debug, Args)]
+struct Restore {
+    /// The trash entry to restore, or a workspace name to restore its latest entry
+    name: String,
+}
+
+impl Trash {
+    pub async fn run(self, state: State) -> eyre::Result<()> {
+        trash::purge(&state).await?;
+
+        match self.command {
+            TrashCommands::List(list) => list.run(state),
+            TrashCommands::Restore(restore) => restore.run(state).await,
+        }
+    }
+}
+
+impl List {
+    fn run(self, state: State) -> eyre::Result<()> {
+        let entries = Entry::list(&state.project_name)?;
+        if entries.is_empty() {
+            eprintln!("The trash is empty.");
+            return Ok(());
+        }
+
+        let mut table = Table::new("{:<}  {:<}  {:<}  {:>}  {:>}  {:<}");
+        table.add_row(
+            Row::new()
+                .with_cell("NAME")
+                .with_cell("WORKSPACE")
+                .with_cell("BRANCH")
+                .with_cell("DELETED")
+                .with_cell("VOLUMES")
+                .with_cell("EXPIRES"),
+        );
+        for entry in entries.iter().rev() {
+            let mut branch = entry.branch.clone().unwrap_or_else(|| "-".into());
+            if entry.snapshot.is_some() {
+                branch.push_str(" (dirty)");
+            }
+            let expires = entry
+                .expires(state.project.trash_days)
+                .strftime("%Y-%m-%d %H:%M");
+            table.add_row(
+                Row::new()
+                    .with_cell(&entry.name)
+                    .with_cell(&entry.workspace)
+                    .with_cell(branch)
+                    .with_cell(format_age(Some(entry.deleted)))
+                    .with_cell(entry.volumes.len())
+                    .with_cell(expires),
+            );
+        }
+        eprint!("{table}");
+        Ok(())
+    }
+}
+
+impl Restore {
+    async fn run(self, state: State) -> eyre::Result<()> {
+        let entry = Entry::find(&state.project_name, &self.name)?;
+        let (workspace, path) = (entry.workspace.clone(), entry.path.clone());
+        entry
+            .restore(&state.docker, &state.project_name, &state.project.path)
+            .await?;
+        eprintln!(
+            "Restored {workspace} at {}; start it with `dc -w {workspace} up`",
+            path.display()
+        );
+        Ok(())
+    }
+}
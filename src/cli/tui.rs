@@ -0,0 +1,356 @@
+use std::env;
+use std::pin::pin;
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+
+use clap::Args;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures::StreamExt;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::time::MissedTickBehavior;
+
+use crate::{
+    cli::State,
+    workspace::{
+        Workspace,
+        live::{LiveWorkspaces, settle},
+    },
+};
+
+mod input;
+mod view;
+
+/// How often to refresh resource use and check for git changes.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// How many lines of action output to keep.
+const OUTPUT_LINES: usize = 1000;
+
+/// Interactive dashboard of the project's workspaces
+#[derive(Debug, Args)]
+pub struct Tui {}
+
+/// What can be done to the selected workspace. All but `Exec` run in the background, with their
+/// output shown in the output pane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Up,
+    Stop,
+    Destroy,
+    Fwd,
+    Exec,
+}
+
+impl Action {
+    /// The `dc` subcommand to run.
+    fn command(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Stop => "stop",
+            Action::Destroy => "destroy",
+            Action::Fwd => "fwd",
+            Action::Exec => "exec",
+        }
+    }
+
+    /// Flags for the subcommand, once the dialog has confirmed the action, so that it doesn't ask
+    /// again.
+    fn confirmed_args(self) -> &'static [&'static str] {
+        match self {
+            Action::Destroy => &["--yes"],
+            Action::Up | Action::Stop | Action::Fwd | Action::Exec => &[],
+        }
+    }
+
+    fn prompt(self, ws: &Workspace) -> Vec<String> {
+        let name = &ws.name;
+        match self {
+            Action::Up => vec![format!("Spin up workspace {name}?")],
+            Action::Stop => vec![format!("Stop workspace {name}?")],
+            Action::Destroy if ws.root => vec![
+                format!("Destroy ROOT workspace {name}?"),
+                "Its containers and volumes will be removed. DATA WILL BE LOST.".into(),
+            ],
+            Action::Destroy => vec![
+                format!("Destroy workspace {name}?"),
+                "Its containers, volumes, and worktree will be removed.".into(),
+            ],
+            Action::Fwd => vec![format!("Forward ports to workspace {name}?")],
+            Action::Exec => vec![format!("Exec into workspace {name}?")],
+        }
+    }
+}
+
+enum Mode {
+    Normal,
+    Confirm(Confirm),
+}
+
+enum Confirm {
+    Run(Action, String),
+    Quit,
+}
+
+/// Messages from a background action.
+enum Output {
+    Line(String),
+    Done(std::io::Result<ExitStatus>),
+}
+
+struct App {
+    live: LiveWorkspaces,
+    selected: usize,
+    mode: Mode,
+    /// The running action, and the workspace it's running on.
+    running: Option<(Action, String)>,
+    output: Vec<String>,
+    /// A message for the status line, cleared on the next key press.
+    message: Option<String>,
+    quit: bool,
+}
+
+impl Tui {
+    pub async fn run(self, state: State) -> eyre::Result<()> {
+        let mut events = pin!(state.docker.container_events());
+        let mut ticker = tokio::time::interval(REFRESH_INTERVAL);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick is immediate, and we've nothing to refresh yet.
+        ticker.tick().await;
+
+        let mut live = LiveWorkspaces::new(&state, false).await?;
+        Workspace::sort(&mut live.workspaces);
+        let mut app = App {
+            live,
+            selected: 0,
+            mode: Mode::Normal,
+            running: None,
+            output: Vec::new(),
+            message: None,
+            quit: false,
+        };
+
+        let (output_tx, mut output_rx) = mpsc::unbounded_channel();
+        let (key_tx, mut key_rx) = mpsc::unbounded_channel();
+        let mut terminal = view::Terminal::enter()?;
+        let mut input = input::Input::start(key_tx);
+
+        while !app.quit {
+            terminal.draw(&app, &state.project_name)?;
+
+            tokio::select! {
+                Some(event) = key_rx.recv() => {
+                    let Event::Key(key) = event else { continue };
+                    if let Some(action) = app.handle_key(key) {
+                        if action == Action::Exec {
+                            let Some(name) = app.selected().map(|ws| ws.name.clone()) else {
+                                continue;
+                            };
+                            input.pause();
+                            terminal.suspend()?;
+                            let status = exec(&state, &name).await;
+                            terminal.resume()?;
+                            input.resume();
+                            match status {
+                                Ok(status) if status.success() => {}
+                                Ok(status) => app.message = Some(format!("dc exec {status}")),
+                                Err(e) => app.message = Some(format!("dc exec failed: {e}")),
+                            }
+                        } else if let Some((action, name)) = app.running.clone() {
+                            app.push_output(format!("$ dc -w {name} {}", action.command()));
+                            spawn(&state, action, &name, output_tx.clone());
+                        }
+                    }
+                }
+                Some(output) = output_rx.recv() => match output {
+                    Output::Line(line) => app.push_output(line),
+                    Output::Done(status) => {
+                        app.finish(status);
+                        app.update(&state, false).await;
+                    }
+                },
+                event = events.next() => {
+                    settle(event, &mut events).await?;
+                    app.update(&state, false).await;
+                }
+                _ = ticker.tick() => app.update(&state, true).await,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl App {
+    fn selected(&self) -> Option<&Workspace> {
+        self.live.workspaces.get(self.selected)
+    }
+
+    fn push_output(&mut self, line: String) {
+        self.output.push(line);
+        let excess = self.output.len().saturating_sub(OUTPUT_LINES);
+        self.output.drain(..excess);
+    }
+
+    /// Re-list workspaces, or with `refresh`, refresh them, keeping the same one selected.
+    async fn update(&mut self, state: &State, refresh: bool) {
+        let selected = self.selected().map(|ws| ws.path.clone());
+        let result = if refresh {
+            self.live.refresh(state).await
+        } else {
+            self.live.relist(state).await
+        };
+        if let Err(e) = result {
+            self.message = Some(format!("failed to list workspaces: {e:#}"));
+        }
+
+        Workspace::sort(&mut self.live.workspaces);
+        self.selected = selected
+            .and_then(|path| self.live.workspaces.iter().position(|ws| ws.path == path))
+            .unwrap_or(self.selected)
+            .min(self.live.workspaces.len().saturating_sub(1));
+    }
+
+    /// Record that the running action finished.
+    fn finish(&mut self, status: std::io::Result<ExitStatus>) {
+        let Some((action, name)) = self.running.take() else {
+            return;
+        };
+        let command = action.command();
+        let summary = match status {
+            Ok(status) if status.success() => format!("dc {command} {name}: done"),
+            Ok(status) => format!("dc {command} {name}: failed, {status}"),
+            Err(e) => format!("dc {command} {name}: failed to run: {e}"),
+        };
+        self.push_output(summary.clone());
+        self.message = Some(summary);
+    }
+
+    /// Handle a key press, returning the action to take, if any. Background actions are recorded
+    /// in `running` before they're returned.
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.kind == KeyEventKind::Release {
+            return None;
+        }
+        self.message = None;
+        let ctrl_c =
+            key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c');
+
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Confirm(confirm) => {
+                if !matches!(key.code, KeyCode::Char('y' | 'Y')) {
+                    return None;
+                }
+                match confirm {
+                    Confirm::Quit => self.quit = true,
+                    Confirm::Run(action, name) => {
+                        self.running = Some((action, name));
+                        return Some(action);
+                    }
+                }
+                None
+            }
+            Mode::Normal => {
+                let action = match key.code {
+                    _ if ctrl_c => return self.request_quit(),
+                    KeyCode::Char('q') | KeyCode::Esc => return self.request_quit(),
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.selected = self.selected.saturating_sub(1);
+                        return None;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        let last = self.live.workspaces.len().saturating_sub(1);
+                        self.selected = (self.selected + 1).min(last);
+                        return None;
+                    }
+                    KeyCode::Home | KeyCode::Char('g') => {
+                        self.selected = 0;
+                        return None;
+                    }
+                    KeyCode::End | KeyCode::Char('G') => {
+                        self.selected = self.live.workspaces.len().saturating_sub(1);
+                        return None;
+                    }
+                    KeyCode::Char('c') => {
+                        self.output.clear();
+                        return None;
+                    }
+                    KeyCode::Char('u') => Action::Up,
+                    KeyCode::Char('s') => Action::Stop,
+                    KeyCode::Char('d') => Action::Destroy,
+                    KeyCode::Char('f') => Action::Fwd,
+                    KeyCode::Char('x') | KeyCode::Enter => Action::Exec,
+                    _ => return None,
+                };
+                let ws = self.selected()?;
+                if let Some((running, name)) = &self.running {
+                    self.message = Some(format!(
+                        "wait for dc {} {name} to finish",
+                        running.command()
+                    ));
+                    return None;
+                }
+                if action == Action::Exec {
+                    return Some(action);
+                }
+                let name = ws.name.clone();
+                self.mode = Mode::Confirm(Confirm::Run(action, name));
+                None
+            }
+        }
+    }
+
+    fn request_quit(&mut self) -> Option<Action> {
+        if self.running.is_some() {
+            self.mode = Mode::Confirm(Confirm::Quit);
+        } else {
+            self.quit = true;
+        }
+        None
+    }
+}
+
+/// The argv prefix for running `dc` on a workspace, as a child process.
+fn dc_command(state: &State, name: &str, action: Action) -> eyre::Result<tokio::process::Command> {
+    let mut cmd = tokio::process::Command::new(env::current_exe()?);
+    cmd.args(["--project", &state.project_name, "--workspace", name])
+        .arg(action.command());
+    Ok(cmd)
+}
+
+/// Run `dc exec` on the terminal, which we have given up.
+async fn exec(state: &State, name: &str) -> eyre::Result<ExitStatus> {
+    Ok(dc_command(state, name, Action::Exec)?.status().await?)
+}
+
+/// Run `action` as a child `dc` process, sending its output, which includes its `Runner` spans,
+/// to `tx`.
+fn spawn(state: &State, action: Action, name: &str, tx: UnboundedSender<Output>) {
+    let cmd = dc_command(state, name, action);
+    tokio::spawn(async move {
+        let result = async {
+            let mut cmd = cmd.map_err(std::io::Error::other)?;
+            // Any prompt we haven't answered with `confirmed_args` gets EOF, and so aborts.
+            let mut child = cmd
+                .args(action.confirmed_args())
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()?;
+
+            let stdout = child.stdout.take().unwrap();
+            let stderr = child.stderr.take().unwrap();
+            let (status, _, _) =
+                tokio::join!(child.wait(), forward(stdout, &tx), forward(stderr, &tx));
+            status
+        }
+        .await;
+        let _ = tx.send(Output::Done(result));
+    });
+}
+
+async fn forward(reader: impl AsyncRead + Unpin, tx: &UnboundedSender<Output>) {
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let _ = tx.send(Output::Line(line));
+    }
+}
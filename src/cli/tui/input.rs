@@ -0,0 +1,67 @@
+//! Terminal input, read on a thread of its own, as crossterm's reads block.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crossterm::event::{self, Event};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// How long to block on input before checking whether we've been paused.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct Input {
+    tx: UnboundedSender<Event>,
+    paused: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Input {
+    pub fn start(tx: UnboundedSender<Event>) -> Self {
+        let mut input = Input {
+            tx,
+            paused: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        };
+        input.resume();
+        input
+    }
+
+    /// Stop reading input, e.g. so that a child process can have it. Even polling for input reads
+    /// it, so this waits for the thread to finish.
+    pub fn pause(&mut self) {
+        self.paused.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.thread.is_some() {
+            return;
+        }
+        self.paused.store(false, Ordering::Relaxed);
+        let tx = self.tx.clone();
+        let paused = self.paused.clone();
+        self.thread = Some(std::thread::spawn(move || {
+            while !paused.load(Ordering::Relaxed) {
+                match event::poll(POLL_INTERVAL) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(_) => return,
+                }
+                let Ok(event) = event::read() else { return };
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+}
+
+impl Drop for Input {
+    fn drop(&mut self) {
+        self.pause();
+    }
+}
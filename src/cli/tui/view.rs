@@ -0,0 +1,225 @@
+//! Drawing the dashboard.
+
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    execute, queue,
+    style::Print,
+    terminal::{
+        self, Clear, ClearType, DisableLineWrap, EnableLineWrap, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
+};
+use itertools::Itertools;
+
+use super::{App, Confirm, Mode};
+use crate::ansi::{BOLD, CYAN, GRAY, RESET, YELLOW};
+use crate::workspace::{
+    Workspace,
+    query::Query,
+    table::{Column, workspace_table},
+};
+
+/// Lines of details about the selected workspace.
+const DETAIL_LINES: usize = 5;
+/// The title, blank lines between sections, the table and output headers, and the status line.
+const CHROME_LINES: usize = 7;
+
+/// The terminal, in raw mode on the alternate screen until dropped.
+pub struct Terminal;
+
+impl Terminal {
+    pub fn enter() -> io::Result<Self> {
+        let mut terminal = Terminal;
+        terminal.resume()?;
+        Ok(terminal)
+    }
+
+    /// Give the terminal back, e.g. for `dc exec`.
+    pub fn suspend(&mut self) -> io::Result<()> {
+        execute!(io::stderr(), EnableLineWrap, Show, LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()
+    }
+
+    pub fn resume(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        // Without wrapping, the terminal clips long lines for us.
+        execute!(io::stderr(), EnterAlternateScreen, Hide, DisableLineWrap)
+    }
+
+    pub fn draw(&mut self, app: &App, project: &str) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        let lines = layout(app, project, width, height);
+
+        let mut out = io::stderr().lock();
+        for (row, line) in lines.iter().enumerate() {
+            queue!(
+                out,
+                MoveTo(0, row as u16),
+                Print(line),
+                Print(RESET),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        queue!(out, Clear(ClearType::FromCursorDown))?;
+        if let Mode::Confirm(confirm) = &app.mode {
+            dialog(&mut out, &prompt(app, confirm), width, height)?;
+        }
+        out.flush()
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.suspend();
+    }
+}
+
+/// The screen's lines, top to bottom.
+fn layout(app: &App, project: &str, width: usize, height: usize) -> Vec<String> {
+    let workspaces = &app.live.workspaces;
+    let mut lines = vec![
+        format!(
+            "{BOLD}dc{RESET} {project} {GRAY}({} workspaces){RESET}",
+            workspaces.len()
+        ),
+        String::new(),
+    ];
+
+    // Split the room we have between the table and the output pane, scrolling the table to keep
+    // the selected workspace in view.
+    let room = height.saturating_sub(CHROME_LINES + DETAIL_LINES);
+    let table_rows = workspaces.len().min((room / 2).max(1));
+    let offset = (app.selected + 1).saturating_sub(table_rows);
+
+    let table = workspace_table(workspaces, &Query::default(), Column::defaults(false)).to_string();
+    let mut table_lines = table.lines();
+    if let Some(header) = table_lines.next() {
+        lines.push(format!("  {header}"));
+    }
+    for (i, line) in table_lines.enumerate().skip(offset).take(table_rows) {
+        let marker = if i == app.selected {
+            format!("{CYAN}\u{276f}{RESET} ")
+        } else {
+            "  ".into()
+        };
+        lines.push(format!("{marker}{line}"));
+    }
+    lines.push(String::new());
+
+    let mut details = app.selected().map(details).unwrap_or_default();
+    details.resize(DETAIL_LINES, String::new());
+    lines.extend(details);
+    lines.push(String::new());
+
+    let title = match &app.running {
+        Some((action, name)) => format!(" dc {} {name} (running) ", action.command()),
+        None => " Output ".into(),
+    };
+    let rule = "\u{2500}".repeat(width.saturating_sub(title.chars().count() + 2));
+    lines.push(format!("{GRAY}\u{2500}\u{2500}{title}{rule}{RESET}"));
+
+    let output_rows = height.saturating_sub(lines.len() + 1);
+    let skip = app.output.len().saturating_sub(output_rows);
+    for line in app.output.iter().skip(skip) {
+        // Carriage returns would move us back to the start of the line.
+        lines.push(line.replace('\r', ""));
+    }
+    lines.resize(height.saturating_sub(1), String::new());
+
+    let status = match &app.message {
+        Some(message) => format!("{YELLOW}{message}{RESET}"),
+        None => format!(
+            "{GRAY}\u{2191}/\u{2193} select  u up  s stop  d destroy  f fwd  x exec  c clear  q quit{RESET}"
+        ),
+    };
+    lines.push(status);
+    lines
+}
+
+fn details(ws: &Workspace) -> Vec<String> {
+    let root = if ws.root { " (root)" } else { "" };
+    let containers = if ws.containers.is_empty() {
+        "none".into()
+    } else {
+        ws.containers
+            .iter()
+            .map(|c| {
+                let service = c.service.as_deref().unwrap_or(&c.id[..c.id.len().min(12)]);
+                format!("{service} {}", c.state)
+            })
+            .join(", ")
+    };
+    let execs = if ws.execs.is_empty() {
+        "none".into()
+    } else {
+        ws.execs
+            .iter()
+            .map(|e| format!("{} {}", e.pid, e.command.join(" ")))
+            .join(", ")
+    };
+    let git = if ws.is_dirty() || ws.git_status.ahead + ws.git_status.behind > 0 {
+        ws.git_status.to_string()
+    } else {
+        "clean".into()
+    };
+    vec![
+        format!(
+            "{BOLD}{}{RESET}{root} {GRAY}{}{RESET}",
+            ws.name,
+            ws.path.display()
+        ),
+        format!("Containers: {containers}"),
+        format!("Execs: {execs}"),
+        format!(
+            "Ports: forwarded {}; published {}",
+            ports(&ws.fwd_ports),
+            ports(&ws.docker_ports)
+        ),
+        format!("Git: {git}"),
+    ]
+}
+
+fn ports(ports: &[u16]) -> String {
+    if ports.is_empty() {
+        "none".into()
+    } else {
+        ports.iter().join(", ")
+    }
+}
+
+fn prompt(app: &App, confirm: &Confirm) -> Vec<String> {
+    let mut lines = match confirm {
+        Confirm::Quit => vec!["Quit?".into(), "The running action will be killed.".into()],
+        Confirm::Run(action, name) => {
+            match app.live.workspaces.iter().find(|ws| &ws.name == name) {
+                Some(ws) => action.prompt(ws),
+                None => vec![format!("dc {} {name}?", action.command())],
+            }
+        }
+    };
+    lines.extend([String::new(), "[y/N]".into()]);
+    lines
+}
+
+/// Draw a box in the middle of the screen with `lines` in it.
+fn dialog(out: &mut impl Write, lines: &[String], width: usize, height: usize) -> io::Result<()> {
+    let inner = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) + 2;
+    let left = width.saturating_sub(inner + 2) / 2;
+    let top = height.saturating_sub(lines.len() + 2) / 2;
+
+    let horizontal = "\u{2500}".repeat(inner);
+    let mut rows = vec![format!("\u{250c}{horizontal}\u{2510}")];
+    for line in lines {
+        let pad = inner - 1 - line.chars().count();
+        rows.push(format!("\u{2502} {BOLD}{line}{RESET}{:pad$}\u{2502}", ""));
+    }
+    rows.push(format!("\u{2514}{horizontal}\u{2518}"));
+
+    for (i, row) in rows.iter().enumerate() {
+        queue!(out, MoveTo(left as u16, (top + i) as u16), Print(row))?;
+    }
+    Ok(())
+}
@@ -1,20 +1,29 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
+use bollard::models::VolumeCreateRequest;
+
 use clap::Args;
+use clap_complete::engine::ArgValueCompleter;
 use color_eyre::owo_colors::OwoColorize;
 use eyre::{WrapErr, eyre};
+use itertools::Itertools;
 use serde_json::json;
 use tracing::info_span;
 use tracing_indicatif::span_ext::IndicatifSpanExt;
+use vec1::Vec1;
 
 use crate::cli::State;
-use crate::cli::copy::copy_volumes;
+use crate::cli::copy::{IMAGE, copy_volumes};
 use crate::cli::exec::exec_interactive;
 use crate::cli::fwd::forward;
-use crate::devcontainer::{Common, Compose};
+use crate::complete;
+use crate::devcontainer::dc_options::dns_label;
+use crate::devcontainer::{Compose, DevContainer};
+use crate::engine::Engine;
 use crate::run::Runner;
 use crate::run::cmd::{Cmd, NamedCmd};
-use crate::worktree;
+use crate::worktree::{self, Checkout};
 
 /// Spin up a devcontainer, or restart an existing one
 #[derive(Debug, Args)]
@@ -28,26 +37,99 @@ pub struct Up {
     forward: bool,
 
     /// Detach worktree rather than creating a branch
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with_all = ["branch", "track"])]
     detach: bool,
 
+    /// Check out this existing branch, rather than one named after the workspace
+    #[arg(
+        short,
+        long,
+        conflicts_with_all = ["from", "track"],
+        add = ArgValueCompleter::new(complete::complete_branch),
+    )]
+    branch: Option<String>,
+
+    /// Start the new branch, or detached worktree, here [default: HEAD]
+    #[arg(long, value_name = "REF", add = ArgValueCompleter::new(complete::complete_ref))]
+    from: Option<String>,
+
+    /// Fetch this remote branch, e.g. origin/feature-x, and check out a new branch tracking it
+    #[arg(
+        long,
+        value_name = "REMOTE/BRANCH",
+        conflicts_with = "from",
+        add = ArgValueCompleter::new(complete::complete_remote_branch),
+    )]
+    track: Option<String>,
+
     /// exec once up with the given command [default: configured defaultExec]
     #[arg(short = 'x', long, num_args = 0.., allow_hyphen_values = true)]
     exec: Option<Vec<String>>,
+
+    /// Enable a compose profile, in addition to configured composeProfiles
+    #[arg(long = "profile", value_name = "PROFILE")]
+    profiles: Vec<String>,
+
+    /// Add a compose file on top of those from devcontainer.json
+    #[arg(short = 'F', long = "file", value_name = "FILE")]
+    files: Vec<PathBuf>,
 }
 
 impl Up {
+    /// Bring a workspace up again, with the `extras` it was last brought up with, e.g. after it
+    /// was renamed.
+    pub(super) fn again(extras: ComposeExtras, forward: bool) -> Self {
+        Up {
+            copy: false,
+            forward,
+            detach: false,
+            branch: None,
+            from: None,
+            track: None,
+            exec: None,
+            profiles: extras.profiles,
+            files: extras.files,
+        }
+    }
+
+    fn checkout(&self) -> Checkout {
+        if self.detach {
+            Checkout::Detach(self.from.clone())
+        } else if let Some(branch) = &self.branch {
+            Checkout::Branch(branch.clone())
+        } else if let Some(remote_branch) = &self.track {
+            Checkout::Track(remote_branch.clone())
+        } else if let Some(from) = &self.from {
+            Checkout::From(from.clone())
+        } else {
+            Checkout::Default
+        }
+    }
+
     pub async fn run(self, state: State) -> eyre::Result<()> {
         let dc = state.devcontainer()?;
         let dc_options = &dc.common.customizations.dc;
 
         let name = state.resolve_workspace().await?;
         let is_root = state.is_root(&name);
+        let mut fresh = false;
         let worktree_path = if is_root {
             state.project.path.clone()
         } else {
             let workspace_dir = dc_options.workspace_dir(&state.project.path);
-            worktree::create(&state.project.path, &workspace_dir, &name, self.detach).await?
+            fresh = !workspace_dir.join(&name).exists();
+            // The name asked for may be branch-style, e.g. `user/thing`, rather than the directory.
+            let requested = state.workspace.as_deref().unwrap_or(&name);
+            let branch = dc_options.branch_name(requested)?;
+            worktree::create(
+                &state.project.path,
+                &workspace_dir,
+                &name,
+                requested,
+                &branch,
+                &self.checkout(),
+            )
+            .await?
         };
 
         // Set up span.
@@ -78,23 +160,47 @@ impl Up {
             unimplemented!();
         };
 
+        let profiles = dc_options
+            .compose_profiles
+            .iter()
+            .chain(&self.profiles)
+            .unique()
+            .cloned()
+            .collect();
+        let files = self
+            .files
+            .iter()
+            .map(|f| {
+                f.canonicalize()
+                    .wrap_err_with(|| format!("compose file not found: {}", f.display()))
+            })
+            .collect::<eyre::Result<_>>()?;
+        let extras = ComposeExtras { profiles, files };
+
         let config_file = worktree_path
             .join(".devcontainer")
             .join("devcontainer.json");
-        let override_file = write_compose_override(
-            compose,
-            &dc.common,
-            &worktree_path,
-            &config_file,
-            &state.project_name,
-            dc_options.mount_git,
-            &state.project.path,
-        )?;
+        let override_file =
+            write_compose_override(compose, &dc, &worktree_path, &config_file, &state, &extras)?;
+
+        let engine = &state.docker.engine;
 
         // Check if the primary container already exists (re-up vs fresh creation)
-        let _already_running = compose_ps_q(compose, &worktree_path, &override_file)
-            .await
-            .is_ok();
+        let _already_running =
+            compose_ps_q(engine, compose, &worktree_path, &override_file, &extras)
+                .await
+                .is_ok();
+
+        // Untracked files, like `.env`, are only copied into new worktrees, so local edits stay.
+        if fresh {
+            let copied =
+                worktree::copy_files(&state.project.path, &worktree_path, &dc_options.copy_files)
+                    .await?;
+            for file in copied {
+                let verb = if file.symlink { "Symlinked" } else { "Copied" };
+                eprintln!("{verb} {}", file.path.display());
+            }
+        }
 
         // initializeCommand runs on the host, from the worktree
         if let Some(ref cmd) = dc.common.initialize_command {
@@ -109,9 +215,38 @@ impl Up {
             copy_volumes(&state, Vec::new(), &root_project, &new_project).await?;
         }
 
-        compose_up(compose, &worktree_path, &override_file).await?;
+        if engine.is_remote() {
+            sync_worktree(&state, &worktree_path).await?;
+        }
+
+        let shared_services: &[String] = if is_root {
+            &[]
+        } else {
+            &dc_options.shared_services
+        };
+        if !shared_services.is_empty() {
+            start_shared_services(engine, compose, &state.project.path, shared_services).await?;
+        }
+
+        if let Some(network) = dc_options.project_network(&state.project_name) {
+            state
+                .docker
+                .ensure_network(&network, &state.project_name)
+                .await?;
+        }
+
+        compose_up(
+            engine,
+            compose,
+            &worktree_path,
+            &override_file,
+            &extras,
+            shared_services,
+        )
+        .await?;
 
-        let container_id = compose_ps_q(compose, &worktree_path, &override_file).await?;
+        let container_id =
+            compose_ps_q(engine, compose, &worktree_path, &override_file, &extras).await?;
         let user = dc.common.remote_user.as_deref();
         let workdir = Some(compose.workspace_folder.as_path());
         let remote_env = &dc.common.remote_env;
@@ -119,11 +254,19 @@ impl Up {
         // Lifecycle commands: create-only commands run only on first creation
         // For now, though, we always recreate.
         if let Some(ref cmd) = dc.common.on_create_command {
-            cmd.run_in_container("onCreateCommand", &container_id, user, workdir, remote_env)
-                .await?;
+            cmd.run_in_container(
+                engine,
+                "onCreateCommand",
+                &container_id,
+                user,
+                workdir,
+                remote_env,
+            )
+            .await?;
         }
         if let Some(ref cmd) = dc.common.update_content_command {
             cmd.run_in_container(
+                engine,
                 "updateContentCommand",
                 &container_id,
                 user,
@@ -134,6 +277,7 @@ impl Up {
         }
         if let Some(ref cmd) = dc.common.post_create_command {
             cmd.run_in_container(
+                engine,
                 "postCreateCommand",
                 &container_id,
                 user,
@@ -143,8 +287,15 @@ impl Up {
             .await?;
         }
         if let Some(ref cmd) = dc.common.post_start_command {
-            cmd.run_in_container("postStartCommand", &container_id, user, workdir, remote_env)
-                .await?;
+            cmd.run_in_container(
+                engine,
+                "postStartCommand",
+                &container_id,
+                user,
+                workdir,
+                remote_env,
+            )
+            .await?;
         }
 
         // Port forward if requested
@@ -154,7 +305,11 @@ impl Up {
 
         // Interactive exec if requested
         if let Some(cmd_args) = self.exec {
+            // Close our connection, and any ssh tunnel, as exec will replace this process.
+            let engine = engine.clone();
+            drop(state);
             exec_interactive(
+                &engine,
                 &container_id,
                 user,
                 workdir,
@@ -181,23 +336,120 @@ pub(crate) fn compose_project_name(worktree_path: &Path) -> String {
         .collect()
 }
 
+/// Where `dc up` writes the compose override file of the compose project `compose_name`.
+pub(crate) fn override_file(compose_name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{compose_name}-override.yml"))
+}
+
+/// The compose volume holding a workspace's files when the engine is remote.
+const WORKSPACE_VOLUME: &str = "dc-workspace";
+
+/// A remote engine can't bind-mount our worktree, so we copy it into a volume instead.
+///
+/// The local worktree wins: its files overwrite those in the volume, but files that only exist in
+/// the volume (e.g. build output) are left alone.
+async fn sync_worktree(state: &State, worktree_path: &Path) -> eyre::Result<()> {
+    let project = compose_project_name(worktree_path);
+    let volume = format!("{project}_{WORKSPACE_VOLUME}");
+
+    // Pre-create the volume with compose labels, so compose manages (and removes) it.
+    let labels = HashMap::from([
+        ("com.docker.compose.project".into(), project.clone()),
+        ("com.docker.compose.volume".into(), WORKSPACE_VOLUME.into()),
+    ]);
+    state
+        .docker
+        .docker
+        .create_volume(VolumeCreateRequest {
+            name: Some(volume.clone()),
+            labels: Some(labels),
+            ..Default::default()
+        })
+        .await?;
+
+    let mut args: Vec<String> = [
+        "/bin/sh",
+        "-c",
+        r#"dir="$1"; shift; tar -C "$dir" -cf - . | "$@""#,
+        "-",
+    ]
+    .map(Into::into)
+    .into();
+    args.push(worktree_path.to_string_lossy().into_owned());
+    args.extend(state.docker.engine.cli());
+    args.extend(
+        [
+            "run",
+            "--rm",
+            "-i",
+            "-v",
+            &format!("{volume}:/workspace"),
+            IMAGE,
+            "tar",
+            "-C",
+            "/workspace",
+            "-xf",
+            "-",
+        ]
+        .map(Into::into),
+    );
+
+    let cmd = NamedCmd {
+        name: "sync worktree",
+        cmd: &Cmd::Args(Vec1::try_from_vec(args)?),
+        dir: None,
+    };
+    Runner::run(cmd).await
+}
+
+/// The name, in a workspace's compose override, of the root workspace's network.
+const SHARED_NETWORK: &str = "dc-shared";
+/// The name, in a workspace's compose override, of the project network.
+const PROJECT_NETWORK: &str = "dc-project";
+
+/// Start the `sharedServices` in the root workspace's compose project, if they aren't already.
+async fn start_shared_services(
+    engine: &Engine,
+    compose: &Compose,
+    project_path: &Path,
+    shared_services: &[String],
+) -> eyre::Result<()> {
+    let mut args = compose_base_args(
+        engine,
+        compose,
+        project_path,
+        None,
+        &ComposeExtras::default(),
+    );
+    args.extend(["up".into(), "-d".into()]);
+    args.extend(shared_services.iter().cloned());
+
+    let cmd = NamedCmd {
+        name: "shared services",
+        cmd: &Cmd::Args(Vec1::try_from_vec(args)?),
+        dir: None,
+    };
+    Runner::run(cmd).await
+}
+
 /// Generate a compose override file with:
 /// * Our own identification labels
 /// * Devcontainer standard labels
 /// * Other devcontainer overrides
 fn write_compose_override(
     compose: &Compose,
-    common: &Common,
+    dc: &DevContainer,
     worktree_path: &Path,
     config_file: &Path,
-    project_name: &str,
-    mount_git: bool,
-    project_path: &Path,
+    state: &State,
+    extras: &ComposeExtras,
 ) -> eyre::Result<PathBuf> {
-    let override_path = std::env::temp_dir().join(format!(
-        "{}-override.yml",
-        compose_project_name(worktree_path)
-    ));
+    let common = &dc.common;
+    let dc_options = &common.customizations.dc;
+    let project_name = &state.project_name;
+    let project_path = &state.project.path;
+    let uses_shared = worktree_path != project_path && !dc_options.shared_services.is_empty();
+    let override_path = override_file(&compose_project_name(worktree_path));
     let local_folder = worktree_path.display();
     let config_file = config_file.display();
 
@@ -207,8 +459,27 @@ fn write_compose_override(
             format!("devcontainer.config_file={config_file}"),
             "dev.dc.managed=true".to_string(),
             format!("dev.dc.project={project_name}"),
+            format!("dev.dc.service={}", compose.service),
         ]
     });
+    let labels = service_obj["labels"]
+        .as_array_mut()
+        .expect("labels is an array");
+    if !extras.profiles.is_empty() {
+        labels.push(json!(format!(
+            "dev.dc.profiles={}",
+            extras.profiles.join(",")
+        )));
+    }
+    if !extras.files.is_empty() {
+        labels.push(json!(format!(
+            "dev.dc.compose_files={}",
+            serde_json::to_string(&extras.files)?
+        )));
+    }
+    if uses_shared {
+        labels.push(json!("dev.dc.shared=true"));
+    }
 
     if !common.container_env.is_empty() {
         service_obj["environment"] = json!(common.container_env);
@@ -230,7 +501,19 @@ fn write_compose_override(
         service_obj["user"] = json!(user);
     }
 
-    if mount_git && worktree_path != project_path {
+    if state.docker.engine.keep_id() {
+        // Rootless podman maps our uid to root in the container by default, which leaves the
+        // bind-mounted worktree owned by root from the container's point of view.
+        service_obj["userns_mode"] = json!("keep-id");
+    }
+
+    let mut overrides = json!({});
+    if state.docker.engine.is_remote() {
+        // Compose merges volumes by target, so this replaces the worktree's bind mount.
+        let mount = format!("{WORKSPACE_VOLUME}:{}", compose.workspace_folder.display());
+        service_obj["volumes"] = json!([mount]);
+        overrides["volumes"] = json!({ WORKSPACE_VOLUME: {} });
+    } else if dc_options.mount_git && worktree_path != project_path {
         let git_dir = project_path.join(".git");
         let mount = format!("{}:{}", git_dir.display(), git_dir.display());
         service_obj["volumes"] = json!([mount]);
@@ -250,25 +533,75 @@ fn write_compose_override(
         service_obj["command"] = json!([]);
     }
 
-    let content = serde_json::to_string_pretty(&json!({
-        "services": { &compose.service: service_obj }
-    }))?;
+    // Listing any network replaces the implicit default, so we have to list that too.
+    let mut service_networks = json!({ "default": {} });
+    let mut networks = json!({});
+    let workspace = worktree_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    if uses_shared {
+        // Join the root workspace's network, where the shared services live.
+        service_networks[SHARED_NETWORK] = json!({ "aliases": [workspace] });
+        networks[SHARED_NETWORK] = json!({
+            "external": true,
+            "name": format!("{}_default", compose_project_name(project_path)),
+        });
+    }
+    if let Some(network) = dc_options.project_network(project_name) {
+        let alias = format!("{}.{}.dc", dns_label(&workspace), dns_label(project_name));
+        service_networks[PROJECT_NETWORK] = json!({ "aliases": [alias] });
+        networks[PROJECT_NETWORK] = json!({ "external": true, "name": network });
+    }
+    if networks.as_object().is_some_and(|n| !n.is_empty()) {
+        service_obj["networks"] = service_networks;
+        overrides["networks"] = networks;
+    }
+
+    overrides["services"] = json!({ &compose.service: service_obj });
+    let content = serde_json::to_string_pretty(&overrides)?;
 
     std::fs::write(&override_path, content)
         .wrap_err_with(|| format!("failed to write {}", override_path.display()))?;
     Ok(override_path)
 }
 
+/// Compose profiles and files chosen for a workspace at `dc up`, beyond its devcontainer.json.
+///
+/// These are recorded as labels on the workspace's container, so that later compose commands
+/// operate on the same set of services.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ComposeExtras {
+    pub profiles: Vec<String>,
+    pub files: Vec<PathBuf>,
+}
+
+impl ComposeExtras {
+    /// The extras recorded by the last `dc up` of the workspace at `worktree_path`.
+    pub async fn recorded(state: &State, worktree_path: &Path) -> eyre::Result<Self> {
+        let containers = state.docker.container_info().await?;
+        let extras = containers
+            .into_iter()
+            .find(|c| c.local_folder == worktree_path && c.primary && c.dc_project.is_some())
+            .map(|c| ComposeExtras {
+                profiles: c.profiles,
+                files: c.compose_files,
+            })
+            .unwrap_or_default();
+        Ok(extras)
+    }
+}
+
+/// The full argv, starting with the compose binary, for running compose against a workspace.
 pub(crate) fn compose_base_args(
+    engine: &Engine,
     compose: &Compose,
     worktree_path: &Path,
     override_file: Option<&Path>,
+    extras: &ComposeExtras,
 ) -> Vec<String> {
-    let mut args = vec![
-        "compose".into(),
-        "-p".into(),
-        compose_project_name(worktree_path),
-    ];
+    let mut args = engine.compose_command();
+    args.extend(["-p".into(), compose_project_name(worktree_path)]);
     for f in &compose.docker_compose_file {
         args.push("-f".into());
         args.push(
@@ -279,55 +612,150 @@ pub(crate) fn compose_base_args(
                 .into_owned(),
         );
     }
+    for f in &extras.files {
+        args.push("-f".into());
+        args.push(f.to_string_lossy().into_owned());
+    }
     if let Some(override_file) = override_file {
         args.push("-f".into());
         args.push(override_file.to_string_lossy().into_owned());
     }
+    for p in &extras.profiles {
+        args.push("--profile".into());
+        args.push(p.clone());
+    }
     args
 }
 
 async fn compose_up(
+    engine: &Engine,
     compose: &Compose,
     worktree_path: &Path,
     override_file: &Path,
+    extras: &ComposeExtras,
+    shared_services: &[String],
 ) -> eyre::Result<()> {
-    let mut args = vec1::vec1!["docker".into()];
-    args.extend(compose_base_args(
-        compose,
-        worktree_path,
-        Some(override_file),
-    ));
+    let base_args = compose_base_args(engine, compose, worktree_path, Some(override_file), extras);
+    let mut args = base_args.clone();
     args.extend(["up".into(), "-d".into(), "--build".into()]);
 
+    let mut to_start = Vec::new();
     if let Some(ref services) = compose.run_services {
-        let mut to_start: Vec<String> = services.clone();
+        to_start = services.clone();
         if !to_start.contains(&compose.service) {
             to_start.push(compose.service.clone());
         }
-        args.extend(to_start);
+        // Services that only exist in a profile can't be in runServices, so add them.
+        if !extras.profiles.is_empty() {
+            let without_profiles = ComposeExtras {
+                profiles: Vec::new(),
+                files: extras.files.clone(),
+            };
+            let default_args = compose_base_args(
+                engine,
+                compose,
+                worktree_path,
+                Some(override_file),
+                &without_profiles,
+            );
+            let (all, default) = tokio::try_join!(
+                compose_services(base_args.clone()),
+                compose_services(default_args)
+            )?;
+            to_start.extend(all.into_iter().filter(|s| !default.contains(s)));
+        }
+        if !shared_services.is_empty() {
+            to_start = with_dependencies(to_start, &compose_dependencies(base_args).await?);
+        }
+    } else if !shared_services.is_empty() {
+        to_start = compose_services(base_args).await?;
+    }
+
+    if !shared_services.is_empty() {
+        // The shared services run in the root workspace, so we must not start our own copies,
+        // even as dependencies. Everything else they'd start is already listed.
+        to_start.retain(|s| !shared_services.contains(s));
+        args.push("--no-deps".into());
     }
+    args.extend(to_start);
 
+    let args = Vec1::try_from_vec(args).wrap_err("empty compose command")?;
     let cmd = NamedCmd {
-        name: "docker compose up",
+        name: "compose up",
         cmd: &Cmd::Args(args),
         dir: None,
     };
     Runner::run(cmd).await
 }
 
+/// The services enabled for the given compose args.
+async fn compose_services(mut args: Vec<String>) -> eyre::Result<Vec<String>> {
+    args.extend(["config".into(), "--services".into()]);
+    let out = tokio::process::Command::new(&args[0])
+        .args(&args[1..])
+        .output()
+        .await?;
+    eyre::ensure!(out.status.success(), "compose config failed");
+    let output = String::from_utf8(out.stdout)?;
+    Ok(output.lines().map(ToString::to_string).collect())
+}
+
+/// Each enabled service's `depends_on`, for the given compose args.
+async fn compose_dependencies(mut args: Vec<String>) -> eyre::Result<HashMap<String, Vec<String>>> {
+    args.extend(["config", "--format", "json"].map(ToString::to_string));
+    let out = tokio::process::Command::new(&args[0])
+        .args(&args[1..])
+        .output()
+        .await?;
+    eyre::ensure!(out.status.success(), "compose config failed");
+    let config: serde_json::Value = serde_json::from_slice(&out.stdout)?;
+    let services = config["services"].as_object().into_iter().flatten();
+    Ok(services
+        .map(|(name, service)| {
+            // Normalized, it's a map, but the short form is a list.
+            let deps = match &service["depends_on"] {
+                serde_json::Value::Object(deps) => deps.keys().cloned().collect(),
+                serde_json::Value::Array(deps) => deps
+                    .iter()
+                    .filter_map(|d| d.as_str().map(ToString::to_string))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            (name.clone(), deps)
+        })
+        .collect())
+}
+
+/// `services`, and everything they depend on, transitively.
+fn with_dependencies(
+    mut services: Vec<String>,
+    dependencies: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    let mut i = 0;
+    while let Some(service) = services.get(i) {
+        let deps = dependencies.get(service).into_iter().flatten();
+        let new: Vec<String> = deps.filter(|d| !services.contains(d)).cloned().collect();
+        services.extend(new);
+        i += 1;
+    }
+    services
+}
+
 async fn compose_ps_q(
+    engine: &Engine,
     compose: &Compose,
     worktree_path: &Path,
     override_file: &Path,
+    extras: &ComposeExtras,
 ) -> eyre::Result<String> {
-    let mut args = compose_base_args(compose, worktree_path, Some(override_file));
+    let mut args = compose_base_args(engine, compose, worktree_path, Some(override_file), extras);
     args.extend(["ps".into(), "-q".into(), compose.service.clone()]);
 
-    let out = tokio::process::Command::new("docker")
-        .args(&args)
+    let out = tokio::process::Command::new(&args[0])
+        .args(&args[1..])
         .output()
         .await?;
-    eyre::ensure!(out.status.success(), "docker compose ps failed");
+    eyre::ensure!(out.status.success(), "compose ps failed");
     let output = String::from_utf8(out.stdout)?;
     let id = output.lines().next().unwrap_or("").trim().to_string();
     if id.is_empty() {
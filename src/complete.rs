@@ -2,6 +2,7 @@ use std::ffi::OsStr;
 
 use clap::{CommandFactory, Parser};
 use clap_complete::engine::CompletionCandidate;
+use itertools::Itertools;
 
 use crate::cli::{Cli, Commands};
 use crate::config::Config;
@@ -34,10 +35,18 @@ fn complete_workspace_inner(current: &OsStr) -> eyre::Result<Vec<CompletionCandi
     let config = Config::load()?;
     let (_, project) = config.project(parse_project_arg())?;
 
+    // Workspaces may be named by the name they were created with, or their branch, too.
     let workspaces = worktree::list_sync(&project.path)
         .unwrap_or_default()
         .into_iter()
-        .filter_map(|path| path.file_name().map(|n| n.to_string_lossy().into_owned()))
+        .flat_map(|wt| {
+            let name = wt
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned());
+            let names = [name, wt.name, wt.branch].into_iter().flatten();
+            names.unique()
+        })
         .filter(|name| is_completion_candidate(&prefix, name))
         .map(CompletionCandidate::new)
         .collect();
@@ -45,6 +54,38 @@ fn complete_workspace_inner(current: &OsStr) -> eyre::Result<Vec<CompletionCandi
     Ok(workspaces)
 }
 
+/// Complete local branch names.
+pub fn complete_branch(current: &OsStr) -> Vec<CompletionCandidate> {
+    complete_branches(current, &[false]).unwrap_or_default()
+}
+
+/// Complete remote-tracking branch names, e.g. `origin/main`.
+pub fn complete_remote_branch(current: &OsStr) -> Vec<CompletionCandidate> {
+    complete_branches(current, &[true]).unwrap_or_default()
+}
+
+/// Complete local, then remote-tracking, branch names.
+pub fn complete_ref(current: &OsStr) -> Vec<CompletionCandidate> {
+    complete_branches(current, &[false, true]).unwrap_or_default()
+}
+
+fn complete_branches(current: &OsStr, remote: &[bool]) -> eyre::Result<Vec<CompletionCandidate>> {
+    let prefix = current.to_string_lossy();
+    let config = Config::load()?;
+    let (_, project) = config.project(parse_project_arg())?;
+
+    let mut candidates = Vec::new();
+    for &remote in remote {
+        candidates.extend(
+            worktree::branches(&project.path, remote)?
+                .into_iter()
+                .filter(|name| is_completion_candidate(&prefix, name))
+                .map(CompletionCandidate::new),
+        );
+    }
+    Ok(candidates)
+}
+
 fn parse_project_arg() -> Option<String> {
     // When completing, the actual args to dc are all after `--`.
     let args = std::env::args().skip_while(|arg| arg != "--").skip(1);
@@ -55,7 +96,8 @@ fn parse_project_arg() -> Option<String> {
         .map(ToOwned::to_owned)
 }
 
-/// Forward completions to `docker __completeNoDesc compose ...` at runtime.
+/// Forward completions to `docker __completeNoDesc compose ...` (or the configured engine's
+/// equivalent) at runtime.
 ///
 /// Extracts the already-typed compose args from the completion command line
 /// then delegates to docker's cobra-based completer.
@@ -67,13 +109,23 @@ pub fn complete_compose(_current: &OsStr) -> Vec<CompletionCandidate> {
 fn complete_compose_inner() -> eyre::Result<Vec<CompletionCandidate>> {
     let prior = compose_prior_args()?;
 
-    // docker compose uses cobra, which provides a method to get its completions:
+    // docker and podman use cobra, which provides a method to get its completions:
     // https://github.com/spf13/cobra/blob/main/completions.go
     let args = ["__complete".into(), "compose".into()]
         .into_iter()
         .chain(prior);
 
-    let output = std::process::Command::new("docker").args(args).output()?;
+    let config = Config::load()?;
+    let mut cli = config.engine.cli();
+    if let Ok((_, project)) = config.project(parse_project_arg())
+        && let Some(context) = project.context
+    {
+        cli.extend(["--context".into(), context]);
+    }
+    let output = std::process::Command::new(&cli[0])
+        .args(&cli[1..])
+        .args(args)
+        .output()?;
 
     let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
     let result = stdout
@@ -3,22 +3,50 @@ use std::path::PathBuf;
 use eyre::{WrapErr, eyre};
 use indexmap::IndexMap;
 use serde::Deserialize;
+use serde_inline_default::serde_inline_default;
+
+use crate::engine::Engine;
 
 pub fn deserialize_shell_path<'de, D: serde::Deserializer<'de>>(d: D) -> Result<PathBuf, D::Error> {
     let s = String::deserialize(d)?;
     Ok(PathBuf::from(shellexpand::tilde(&s).as_ref()))
 }
 
+pub fn deserialize_shell_path_opt<'de, D: serde::Deserializer<'de>>(
+    d: D,
+) -> Result<Option<PathBuf>, D::Error> {
+    Option::<String>::deserialize(d)
+        .map(|o| o.map(|s| PathBuf::from(shellexpand::tilde(&s).as_ref())))
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub projects: IndexMap<String, Project>,
+    /// The container engine to use.
+    #[serde(default)]
+    pub engine: Engine,
 }
 
+#[serde_inline_default]
 #[derive(Debug, Deserialize)]
 pub struct Project {
     #[serde(deserialize_with = "deserialize_shell_path")]
     pub path: PathBuf,
+    /// The docker context to run this project's containers in, e.g. a remote build box.
+    #[serde(default)]
+    pub context: Option<String>,
+    /// The branch that workspaces' branches are merged into, to tell which are done. Defaults to
+    /// the root workspace's branch, then `origin/HEAD`.
+    #[serde(default)]
+    pub base_branch: Option<String>,
+    /// Whether `dc destroy` deletes the workspace's branch by default.
+    #[serde(default)]
+    pub delete_branch: bool,
+    /// How many days `dc destroy` keeps destroyed workspaces in the trash, so they can be
+    /// restored. With 0, they aren't kept.
+    #[serde_inline_default(7)]
+    pub trash_days: u32,
 }
 
 impl Config {
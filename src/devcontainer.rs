@@ -225,6 +225,21 @@ pub struct Common {
     pub customizations: Customizations,
 }
 
+impl Common {
+    /// The environment of processes we spawn in the container: `containerEnv`, overridden by
+    /// `remoteEnv`, where a null value unsets a variable.
+    pub fn env(&self) -> IndexMap<String, String> {
+        let mut env = self.container_env.clone();
+        for (key, value) in &self.remote_env {
+            match value {
+                Some(value) => env.insert(key.clone(), value.clone()),
+                None => env.shift_remove(key),
+            };
+        }
+        env
+    }
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, Default)]
 pub struct Customizations {
     #[serde(default)]
@@ -410,3 +425,28 @@ pub enum NonComposeShutdownAction {
     #[default]
     StopContainer,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env() {
+        let common: Common = serde_json::from_str(
+            r#"{
+                "containerEnv": {"A": "1", "B": "2", "C": "3"},
+                "remoteEnv": {"B": "two", "C": null, "D": "4"}
+            }"#,
+        )
+        .unwrap();
+        let env: Vec<_> = common.env().into_iter().collect();
+        assert_eq!(
+            env,
+            [
+                ("A".into(), "1".into()),
+                ("B".into(), "two".into()),
+                ("D".into(), "4".into()),
+            ]
+        );
+    }
+}
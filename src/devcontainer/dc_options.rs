@@ -4,15 +4,9 @@ use serde::{Deserialize, Serialize};
 use serde_inline_default::serde_inline_default;
 use serde_with::serde_as;
 
+use crate::config::deserialize_shell_path_opt;
 use crate::run::cmd::Cmd;
 
-fn deserialize_shell_path_opt<'de, D: serde::Deserializer<'de>>(
-    d: D,
-) -> Result<Option<PathBuf>, D::Error> {
-    Option::<String>::deserialize(d)
-        .map(|o| o.map(|s| PathBuf::from(shellexpand::tilde(&s).as_ref())))
-}
-
 #[serde_as]
 #[serde_inline_default]
 #[derive(Deserialize, Serialize, Debug, Clone, Default)]
@@ -23,6 +17,21 @@ pub struct DcOptions {
     worktree_folder: Option<PathBuf>,
     /// The default volumes to be copied with `dc copy` and `dc up --copy`.
     pub default_copy_volumes: Option<Vec<String>>,
+    /// Compose profiles to enable for every workspace. More can be added with `dc up --profile`.
+    #[serde(default)]
+    pub compose_profiles: Vec<String>,
+    /// Services that run once, in the root workspace, and are shared by all other workspaces.
+    ///
+    /// Other workspaces don't start their own copies; instead their primary container joins the
+    /// root workspace's network, where it can reach these services by name.
+    #[serde(default)]
+    pub shared_services: Vec<String>,
+    /// Whether to connect every workspace's primary container to a network shared by the project.
+    ///
+    /// On it, each is reachable as `<workspace>.<project>.dc`, so e.g. a frontend in one worktree
+    /// can talk to a backend in another.
+    #[serde(default)]
+    pub project_network: bool,
     /// Whether to mount the project's git directory into each workspace's devcontainer.
     ///
     /// Git worktrees have a simple `.git` file that points to the actual `.git` directory. If that
@@ -31,9 +40,64 @@ pub struct DcOptions {
     /// both inside and out of the devcontainer.
     #[serde_inline_default(true)]
     pub mount_git: bool,
+    /// The name of the branch `dc up` creates for a new workspace, where `{name}` is the
+    /// workspace's name and `{user}` is `$USER`, e.g. `"{user}/{name}"`. Defaults to `"{name}"`.
+    pub branch_template: Option<String>,
+    /// Untracked files, such as gitignored `.env` files, that `dc up` copies from the root
+    /// worktree into each new worktree.
+    #[serde(default)]
+    pub copy_files: Vec<CopyFile>,
+}
+
+/// A glob, relative to the worktree, of files for `dc up` to copy into new worktrees, e.g.
+/// `"config/*.local.toml"`, or `{ "glob": ".vscode/**", "symlink": true }` to symlink them instead.
+///
+/// A glob matching a directory matches everything in it.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum CopyFile {
+    Glob(String),
+    Options {
+        glob: String,
+        #[serde(default)]
+        symlink: bool,
+    },
+}
+
+impl CopyFile {
+    pub fn glob(&self) -> &str {
+        match self {
+            CopyFile::Glob(glob) | CopyFile::Options { glob, .. } => glob,
+        }
+    }
+
+    /// Whether to symlink the files, rather than copy them.
+    pub fn symlink(&self) -> bool {
+        matches!(self, CopyFile::Options { symlink: true, .. })
+    }
 }
 
 impl DcOptions {
+    /// The name of the project network, if enabled.
+    pub fn project_network(&self, project_name: &str) -> Option<String> {
+        self.project_network
+            .then(|| format!("dc-{}", dns_label(project_name)))
+    }
+
+    /// The branch to create for the workspace called `name`.
+    pub fn branch_name(&self, name: &str) -> eyre::Result<String> {
+        let Some(template) = &self.branch_template else {
+            return Ok(name.to_string());
+        };
+        let mut branch = template.replace("{name}", name);
+        if branch.contains("{user}") {
+            let user = std::env::var("USER")
+                .map_err(|_| eyre::eyre!("branchTemplate uses {{user}}, but $USER is not set"))?;
+            branch = branch.replace("{user}", &user);
+        }
+        Ok(branch)
+    }
+
     pub fn workspace_dir(&self, project_path: &Path) -> PathBuf {
         let dir = self.worktree_folder.clone().unwrap_or("/tmp/".into());
         if dir.is_relative() {
@@ -43,3 +107,53 @@ impl DcOptions {
         }
     }
 }
+
+/// Lowercase, keeping only `[a-z0-9-]`, for use in DNS names.
+pub fn dns_label(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branch_names() {
+        let mut options = DcOptions::default();
+        assert_eq!(options.branch_name("user/thing").unwrap(), "user/thing");
+
+        options.branch_template = Some("feature/{name}".into());
+        assert_eq!(options.branch_name("thing").unwrap(), "feature/thing");
+    }
+
+    #[test]
+    fn copy_files() {
+        let options: DcOptions = serde_json::from_str(
+            r#"{ "copyFiles": [".env", { "glob": ".vscode/**", "symlink": true }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            options.copy_files,
+            [
+                CopyFile::Glob(".env".into()),
+                CopyFile::Options {
+                    glob: ".vscode/**".into(),
+                    symlink: true
+                },
+            ]
+        );
+        assert!(!options.copy_files[0].symlink());
+        assert_eq!(options.copy_files[1].glob(), ".vscode/**");
+    }
+
+    #[test]
+    fn dns_labels() {
+        assert_eq!(dns_label("Feature_X"), "feature-x");
+        assert_eq!(dns_label("_best.project_"), "best-project");
+    }
+}
@@ -3,6 +3,7 @@ use std::path::Path;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
+use crate::engine::Engine;
 use crate::run::Runner;
 use crate::run::cmd::{Cmd, NamedCmd};
 use crate::run::docker_exec::DockerExec;
@@ -35,6 +36,7 @@ impl LifecycleCommand {
 
     pub async fn run_in_container(
         &self,
+        engine: &Engine,
         name: &str,
         container: &str,
         user: Option<&str>,
@@ -44,6 +46,7 @@ impl LifecycleCommand {
         match self {
             LifecycleCommand::Single(cmd) => {
                 let exec = DockerExec {
+                    engine,
                     name,
                     container,
                     cmd,
@@ -55,6 +58,7 @@ impl LifecycleCommand {
             }
             LifecycleCommand::Parallel(map) => {
                 let execs = map.iter().map(|(cmd_name, cmd)| DockerExec {
+                    engine,
                     name: cmd_name,
                     container,
                     cmd,
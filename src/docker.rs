@@ -2,13 +2,24 @@ use std::{collections::HashMap, path::PathBuf};
 
 use bollard::{
     Docker,
-    query_parameters::{ListContainersOptions, StatsOptions},
+    models::{
+        ContainerCpuStats, ContainerStatsResponse, ContainerSummary, EventMessage,
+        NetworkCreateRequest, Volume,
+    },
+    query_parameters::{
+        DataUsageOptions, EventsOptions, InspectContainerOptions, ListContainersOptions,
+        ListVolumesOptions, StatsOptions,
+    },
     secret::ContainerSummaryStateEnum,
 };
 use derive_more::{Add, Sum};
 use eyre::{WrapErr, eyre};
-use futures::{StreamExt, future::try_join_all};
+use futures::{Stream, StreamExt, TryStreamExt, future, future::try_join_all};
 use itertools::Itertools;
+use serde::Serialize;
+use tokio::process::Child;
+
+use crate::engine::Engine;
 
 #[derive(Debug)]
 pub struct ContainerInfo {
@@ -18,40 +29,157 @@ pub struct ContainerInfo {
     pub dc_project: Option<String>,
     pub created: Option<i64>,
     pub host_ports: Vec<u16>,
+    /// Compose profiles that `dc up` enabled.
+    pub profiles: Vec<String>,
+    /// Extra compose files that `dc up` was given.
+    pub compose_files: Vec<PathBuf>,
+    /// Whether this container uses the root workspace's shared services.
+    pub uses_shared: bool,
+    /// The compose service this container runs.
+    pub service: Option<String>,
+    /// Whether this container runs the devcontainer's primary service, as opposed to one of the
+    /// other services of its compose project.
+    pub primary: bool,
+}
+
+impl ContainerInfo {
+    /// Parse a listed container, from a workspace at `local_folder`.
+    fn new(
+        c: ContainerSummary,
+        local_folder: PathBuf,
+        dc_project: Option<String>,
+    ) -> eyre::Result<Self> {
+        let mut labels = c.labels.ok_or_else(|| eyre!("container missing labels"))?;
+        let profiles = labels
+            .remove("dev.dc.profiles")
+            .map(|p| p.split(',').map(ToString::to_string).collect())
+            .unwrap_or_default();
+        let compose_files = labels
+            .remove("dev.dc.compose_files")
+            .map(|f| serde_json::from_str(&f))
+            .transpose()
+            .wrap_err("invalid dev.dc.compose_files label")?
+            .unwrap_or_default();
+        let uses_shared = labels.contains_key("dev.dc.shared");
+        let service = labels.remove("com.docker.compose.service");
+        // Only the primary service gets devcontainer labels. Since we record its name, also
+        // check that it matches, in case a compose file sets these labels itself.
+        let primary = labels.contains_key("devcontainer.local_folder")
+            && labels
+                .remove("dev.dc.service")
+                .is_none_or(|s| Some(&s) == service.as_ref());
+        let id = c.id.ok_or_else(|| eyre!("container missing id"))?;
+        let state = c.state.ok_or_else(|| eyre!("container missing state"))?;
+
+        let host_ports: Vec<u16> = c
+            .ports
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|p| p.public_port)
+            .collect();
+
+        Ok(ContainerInfo {
+            id,
+            state,
+            local_folder,
+            dc_project,
+            created: c.created,
+            host_ports,
+            profiles,
+            compose_files,
+            uses_shared,
+            service,
+            primary,
+        })
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ExecSession {
     pub pid: u32,
     pub command: Vec<String>,
 }
 
-#[derive(Debug, Clone, Add, Sum)]
+#[derive(Debug, Clone, Default, Add, Sum, Serialize)]
 pub struct Stats {
     /// Current memory use in bytes.
     pub ram: u64,
+    /// CPU use, as a percentage of one CPU. Only collected with detailed stats.
+    pub cpu: f64,
+    /// Total bytes read from block devices. Only collected with detailed stats.
+    pub block_read: u64,
+    /// Total bytes written to block devices. Only collected with detailed stats.
+    pub block_write: u64,
+    /// Total bytes received over the network. Only collected with detailed stats.
+    pub net_rx: u64,
+    /// Total bytes sent over the network. Only collected with detailed stats.
+    pub net_tx: u64,
+    /// Size of the writable layer in bytes. Only collected with detailed stats.
+    pub disk: u64,
 }
 
 pub struct DockerClient {
     // TODO: Instead of making this public, we should move all docker functionality we need to this
     // module.
     pub docker: Docker,
+    pub engine: Engine,
+    /// An ssh tunnel to a remote engine, kept alive as long as we are.
+    _tunnel: Option<Child>,
 }
 
 impl DockerClient {
-    pub async fn new() -> eyre::Result<Self> {
-        let docker =
-            Docker::connect_with_local_defaults().wrap_err("failed to connect to Docker")?;
-        Ok(Self { docker })
+    pub async fn new(engine: Engine) -> eyre::Result<Self> {
+        let (docker, tunnel) = engine.connect().await?;
+        Ok(Self {
+            docker,
+            engine,
+            _tunnel: tunnel,
+        })
     }
 
-    /// Return all containers labeled with `devcontainer.local_folder`.
+    /// Return all containers labeled with `devcontainer.local_folder`, along with the other
+    /// containers of their compose projects.
     pub async fn container_info(&self) -> eyre::Result<Vec<ContainerInfo>> {
-        let mut filters = HashMap::new();
-        filters.insert(
-            "label".to_string(),
-            vec!["devcontainer.local_folder".to_string()],
-        );
+        let (devcontainers, composed) = tokio::try_join!(
+            self.containers_with_label("devcontainer.local_folder"),
+            self.containers_with_label("com.docker.compose.project"),
+        )?;
+
+        let mut result = Vec::new();
+        // The local folder and dc project of each devcontainer's compose project.
+        let mut projects = HashMap::new();
+        for c in devcontainers {
+            let mut labels = c.labels.clone().unwrap_or_default();
+            let local_folder: PathBuf = labels.remove("devcontainer.local_folder")
+                .ok_or_else(|| eyre!("container was filtered by devcontainer.local_folder, but does not have that label"))?.into();
+            let dc_project = labels.remove("dev.dc.project");
+            if let Some(project) = labels.get("com.docker.compose.project") {
+                projects.insert(project.clone(), (local_folder.clone(), dc_project.clone()));
+            }
+            result.push(ContainerInfo::new(c, local_folder, dc_project)?);
+        }
+
+        for c in composed {
+            let labels = c.labels.as_ref();
+            let project = labels.and_then(|l| l.get("com.docker.compose.project"));
+            let Some((local_folder, dc_project)) = project.and_then(|p| projects.get(p)) else {
+                continue;
+            };
+            if result.iter().any(|r| Some(&r.id) == c.id.as_ref()) {
+                continue;
+            }
+            result.push(ContainerInfo::new(
+                c,
+                local_folder.clone(),
+                dc_project.clone(),
+            )?);
+        }
+
+        Ok(result)
+    }
+
+    async fn containers_with_label(&self, label: &str) -> eyre::Result<Vec<ContainerSummary>> {
+        let filters = HashMap::from([("label".to_string(), vec![label.to_string()])]);
         let containers = self
             .docker
             .list_containers(Some(ListContainersOptions {
@@ -60,56 +188,184 @@ impl DockerClient {
                 ..Default::default()
             }))
             .await?;
+        Ok(containers)
+    }
 
-        let mut result = Vec::new();
-        for c in containers {
-            let mut labels = c.labels.ok_or_else(|| eyre!("container missing labels"))?;
-            let local_folder = labels.remove("devcontainer.local_folder")
-                .ok_or_else(|| eyre!("container was filtered by devcontainer.local_folder, but does not have that label"))?.into();
-            let dc_project = labels.remove("dev.dc.project");
-            let id = c.id.ok_or_else(|| eyre!("container missing id"))?;
-            let state = c.state.ok_or_else(|| eyre!("container missing state"))?;
-
-            let host_ports: Vec<u16> = c
-                .ports
-                .unwrap_or_default()
-                .iter()
-                .filter_map(|p| p.public_port)
-                .collect();
-
-            result.push(ContainerInfo {
-                id,
-                state,
-                local_folder,
-                dc_project,
-                created: c.created,
-                host_ports,
-            });
+    /// Create the project network, unless it already exists.
+    pub async fn ensure_network(&self, name: &str, project: &str) -> eyre::Result<()> {
+        if self.docker.inspect_network(name, None).await.is_ok() {
+            return Ok(());
         }
+        let labels = HashMap::from([
+            ("dev.dc.network".to_string(), "true".to_string()),
+            ("dev.dc.project".to_string(), project.to_string()),
+        ]);
+        self.docker
+            .create_network(NetworkCreateRequest {
+                name: name.to_string(),
+                driver: Some("bridge".into()),
+                attachable: Some(true),
+                labels: Some(labels),
+                ..Default::default()
+            })
+            .await
+            .wrap_err_with(|| format!("failed to create network {name}"))?;
+        Ok(())
+    }
 
-        Ok(result)
+    /// Remove the given project network if nothing is connected to it anymore, and no workspace
+    /// of its project has containers, even stopped ones, that would join it when started.
+    pub async fn remove_unused_network(&self, name: &str) -> eyre::Result<()> {
+        let Ok(network) = self.docker.inspect_network(name, None).await else {
+            return Ok(());
+        };
+        if network.containers.is_some_and(|c| !c.is_empty()) {
+            return Ok(());
+        }
+        if let Some(project) = network
+            .labels
+            .as_ref()
+            .and_then(|l| l.get("dev.dc.project"))
+        {
+            let label = format!("dev.dc.project={project}");
+            if !self.containers_with_label(&label).await?.is_empty() {
+                return Ok(());
+            }
+        }
+        self.docker
+            .remove_network(name)
+            .await
+            .wrap_err_with(|| format!("failed to remove network {name}"))
     }
 
-    pub async fn stats(&self, container_id: &str) -> eyre::Result<Stats> {
+    /// Resource use of the given container.
+    ///
+    /// Only `ram` is collected unless `detailed`, as measuring CPU use takes a second, and the disk
+    /// size can take a while for large containers.
+    pub async fn stats(&self, container_id: &str, detailed: bool) -> eyre::Result<Stats> {
         let mut stream = self.docker.stats(
             container_id,
             Some(StatsOptions {
                 stream: false,
-                one_shot: true,
+                // With one_shot, we don't get the previous CPU sample we need to compute CPU use.
+                one_shot: !detailed,
             }),
         );
-        match stream.next().await {
-            Some(Ok(stats)) => {
-                let ram = stats
-                    .memory_stats
-                    .as_ref()
-                    .and_then(|m| m.usage)
-                    .unwrap_or_default();
-                Ok(Stats { ram })
-            }
-            Some(Err(e)) => Err(e.into()),
-            None => Err(eyre!("no stats response for container {container_id}")),
+        let stats = match stream.next().await {
+            Some(Ok(stats)) => stats,
+            Some(Err(e)) => return Err(e.into()),
+            None => return Err(eyre!("no stats response for container {container_id}")),
+        };
+        let ram = stats
+            .memory_stats
+            .as_ref()
+            .and_then(|m| m.usage)
+            .unwrap_or_default();
+        if !detailed {
+            return Ok(Stats {
+                ram,
+                ..Default::default()
+            });
+        }
+
+        let blkio = |op: &str| -> u64 {
+            stats
+                .blkio_stats
+                .as_ref()
+                .and_then(|b| b.io_service_bytes_recursive.as_ref())
+                .into_iter()
+                .flatten()
+                .filter(|e| e.op.as_deref().is_some_and(|o| o.eq_ignore_ascii_case(op)))
+                .filter_map(|e| e.value)
+                .sum()
+        };
+        let networks = stats.networks.iter().flat_map(|n| n.values());
+        let net_rx = networks.clone().filter_map(|n| n.rx_bytes).sum();
+        let net_tx = networks.filter_map(|n| n.tx_bytes).sum();
+
+        let info = self
+            .docker
+            .inspect_container(container_id, Some(InspectContainerOptions { size: true }))
+            .await
+            .wrap_err_with(|| format!("failed to inspect container {container_id}"))?;
+
+        Ok(Stats {
+            ram,
+            cpu: cpu_percent(&stats),
+            block_read: blkio("read"),
+            block_write: blkio("write"),
+            net_rx,
+            net_tx,
+            disk: info.size_rw.unwrap_or_default().max(0) as u64,
+        })
+    }
+
+    /// The total size of named volumes, by the compose project they belong to.
+    pub async fn volume_sizes(&self) -> eyre::Result<HashMap<String, u64>> {
+        let df = self
+            .docker
+            .df(Some(DataUsageOptions {
+                _type: Some(vec!["volume".into()]),
+                ..Default::default()
+            }))
+            .await
+            .wrap_err("failed to get volume sizes")?;
+
+        let mut sizes = HashMap::new();
+        let items = df
+            .volumes_disk_usage
+            .and_then(|v| v.items)
+            .unwrap_or_default();
+        for item in items {
+            let Ok(volume) = serde_json::from_value::<Volume>(item) else {
+                continue;
+            };
+            let Some(project) = volume.labels.get("com.docker.compose.project") else {
+                continue;
+            };
+            let size = volume.usage_data.map_or(0, |u| u.size.max(0) as u64);
+            *sizes.entry(project.clone()).or_default() += size;
         }
+        Ok(sizes)
+    }
+
+    /// The compose project's volumes, by name.
+    pub async fn project_volumes(&self, project: &str) -> eyre::Result<Vec<Volume>> {
+        let filters = HashMap::from([(
+            "label".to_string(),
+            vec![format!("com.docker.compose.project={project}")],
+        )]);
+        let volumes = self
+            .docker
+            .list_volumes(Some(ListVolumesOptions {
+                filters: Some(filters),
+            }))
+            .await
+            .wrap_err_with(|| format!("failed to list volumes of {project}"))?;
+        let mut volumes = volumes.volumes.unwrap_or_default();
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(volumes)
+    }
+
+    /// A stream of events for the containers we list: devcontainers, and those dc starts, like
+    /// `dc fwd` sidecars.
+    pub fn container_events(&self) -> impl Stream<Item = eyre::Result<EventMessage>> + use<> {
+        let filters = HashMap::from([("type".to_string(), vec!["container".to_string()])]);
+        self.docker
+            .events(Some(EventsOptions {
+                filters: Some(filters),
+                ..Default::default()
+            }))
+            .map_err(eyre::Report::from)
+            .try_filter(|event| {
+                let labels = event.actor.as_ref().and_then(|a| a.attributes.as_ref());
+                let ours = labels.is_some_and(|labels| {
+                    labels
+                        .keys()
+                        .any(|k| k == "devcontainer.local_folder" || k.starts_with("dev.dc."))
+                });
+                future::ready(ours)
+            })
     }
 
     /// Ports forwarded by `dc fwd`.
@@ -179,6 +435,21 @@ impl DockerClient {
         Ok(ports)
     }
 
+    /// When the container last stopped, if it has.
+    pub async fn finished_at(&self, container_id: &str) -> eyre::Result<Option<jiff::Timestamp>> {
+        let info = self
+            .docker
+            .inspect_container(container_id, None)
+            .await
+            .wrap_err_with(|| format!("failed to inspect container {container_id}"))?;
+        // Containers that never stopped have a zero time, in year 1.
+        Ok(info
+            .state
+            .and_then(|s| s.finished_at)
+            .and_then(|t| t.parse::<jiff::Timestamp>().ok())
+            .filter(|t| t.as_second() > 0))
+    }
+
     pub async fn execs(&self, container_id: &str) -> eyre::Result<Vec<ExecSession>> {
         let info = self
             .docker
@@ -211,3 +482,73 @@ impl DockerClient {
         Ok(execs)
     }
 }
+
+/// CPU use as a percentage of one CPU, from the change since the previous sample, as `docker stats`
+/// computes it.
+fn cpu_percent(stats: &ContainerStatsResponse) -> f64 {
+    let total = |cpu: Option<&ContainerCpuStats>| {
+        cpu.and_then(|c| c.cpu_usage.as_ref())
+            .and_then(|u| u.total_usage)
+            .unwrap_or_default()
+    };
+    let system =
+        |cpu: Option<&ContainerCpuStats>| cpu.and_then(|c| c.system_cpu_usage).unwrap_or_default();
+    let (cpu, precpu) = (stats.cpu_stats.as_ref(), stats.precpu_stats.as_ref());
+
+    let cpu_delta = total(cpu).saturating_sub(total(precpu));
+    let system_delta = system(cpu).saturating_sub(system(precpu));
+    if cpu_delta == 0 || system_delta == 0 {
+        return 0.0;
+    }
+    let online_cpus = cpu
+        .and_then(|c| {
+            c.online_cpus.or_else(|| {
+                c.cpu_usage
+                    .as_ref()
+                    .and_then(|u| u.percpu_usage.as_ref())
+                    .map(|p| p.len() as u32)
+            })
+        })
+        .unwrap_or(1);
+
+    cpu_delta as f64 / system_delta as f64 * f64::from(online_cpus) * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use bollard::models::ContainerCpuUsage;
+
+    use super::*;
+
+    fn cpu_stats(total: u64, system: u64) -> ContainerCpuStats {
+        ContainerCpuStats {
+            cpu_usage: Some(ContainerCpuUsage {
+                total_usage: Some(total),
+                ..Default::default()
+            }),
+            system_cpu_usage: Some(system),
+            online_cpus: Some(4),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cpu() {
+        let stats = ContainerStatsResponse {
+            precpu_stats: Some(cpu_stats(1_000, 100_000)),
+            cpu_stats: Some(cpu_stats(6_000, 200_000)),
+            ..Default::default()
+        };
+        assert_eq!(cpu_percent(&stats), 20.0);
+    }
+
+    #[test]
+    fn cpu_without_change() {
+        let stats = ContainerStatsResponse {
+            precpu_stats: Some(cpu_stats(6_000, 200_000)),
+            cpu_stats: Some(cpu_stats(6_000, 200_000)),
+            ..Default::default()
+        };
+        assert_eq!(cpu_percent(&stats), 0.0);
+    }
+}
@@ -0,0 +1,277 @@
+//! The container engine we talk to: either Docker or Podman.
+//!
+//! We use the engine in two ways: through its API socket (via bollard), and by shelling out to its
+//! CLI for `compose`, interactive `exec`, and completions. Both are configured here.
+//!
+//! The engine may be remote, e.g. via a docker context pointing at `ssh://` or `tcp://`. In that
+//! case, worktrees can't be bind-mounted, and forwarded ports need an ssh tunnel back to this
+//! machine.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bollard::Docker;
+use eyre::{WrapErr, eyre};
+use serde::Deserialize;
+use tokio::process::{Child, Command};
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineKind {
+    #[default]
+    Docker,
+    Podman,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Engine {
+    pub kind: EngineKind,
+    /// Path to the engine's CLI. Defaults to `docker` or `podman`, found on `PATH`.
+    #[serde(deserialize_with = "crate::config::deserialize_shell_path_opt")]
+    binary: Option<PathBuf>,
+    /// The API socket to connect to, e.g. `unix:///run/user/1000/podman/podman.sock` or
+    /// `tcp://127.0.0.1:2375`. A bare path is treated as a unix socket.
+    ///
+    /// Defaults to `DOCKER_HOST`, then the engine's standard socket.
+    socket: Option<String>,
+    /// The command to run for `compose`, e.g. `["podman-compose"]`. Defaults to `<binary> compose`.
+    compose: Option<Vec<String>>,
+    /// Podman only: run the primary service with `userns_mode: keep-id`, so that files in the
+    /// bind-mounted worktree are owned by you both inside and out of the container.
+    keep_id: Option<bool>,
+    /// The docker context to use, set per-project.
+    #[serde(skip)]
+    context: Option<String>,
+}
+
+impl Engine {
+    /// The engine's CLI binary.
+    pub fn binary(&self) -> String {
+        match &self.binary {
+            Some(path) => path.to_string_lossy().into_owned(),
+            None => match self.kind {
+                EngineKind::Docker => "docker".into(),
+                EngineKind::Podman => "podman".into(),
+            },
+        }
+    }
+
+    /// The argv prefix for running engine commands, including any context selection.
+    pub fn cli(&self) -> Vec<String> {
+        let mut args = vec![self.binary()];
+        if let Some(context) = &self.context {
+            args.extend(["--context".into(), context.clone()]);
+        }
+        args
+    }
+
+    /// The argv prefix for running compose commands.
+    pub fn compose_command(&self) -> Vec<String> {
+        match &self.compose {
+            Some(cmd) if !cmd.is_empty() => cmd.clone(),
+            _ => {
+                let mut args = self.cli();
+                args.push("compose".into());
+                args
+            }
+        }
+    }
+
+    /// Use the given docker context, resolving its endpoint so we can connect to the same host.
+    pub fn with_context(mut self, context: String) -> eyre::Result<Self> {
+        eyre::ensure!(
+            self.kind == EngineKind::Docker,
+            "docker contexts are only supported with the docker engine"
+        );
+        let out = std::process::Command::new(self.binary())
+            .args(["context", "inspect", &context])
+            .args(["--format", "{{.Endpoints.docker.Host}}"])
+            .output()
+            .wrap_err_with(|| format!("failed to inspect docker context {context:?}"))?;
+        eyre::ensure!(
+            out.status.success(),
+            "failed to inspect docker context {context:?}: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+        let host = String::from_utf8(out.stdout)?.trim().to_string();
+        eyre::ensure!(!host.is_empty(), "docker context {context:?} has no host");
+
+        self.socket = Some(host);
+        self.context = Some(context);
+        Ok(self)
+    }
+
+    /// The hostname of the engine, if it is on another machine.
+    pub fn remote_host(&self) -> Option<String> {
+        let socket = self.socket()?;
+        let (scheme, rest) = socket.split_once("://")?;
+        if !matches!(scheme, "tcp" | "http" | "https" | "ssh") {
+            return None;
+        }
+        let authority = rest.split('/').next().unwrap_or(rest);
+        let host = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+        let host = match host.strip_prefix('[') {
+            // IPv6, e.g. `[::1]:2375`
+            Some(h) => h.split(']').next().unwrap_or(h),
+            None => host.split(':').next().unwrap_or(host),
+        };
+        Some(host.to_string())
+    }
+
+    /// Whether we reach the engine over the network, so can't share files with it. A dockerd on a
+    /// local TCP socket counts, which makes it a stand-in for a remote one.
+    pub fn is_remote(&self) -> bool {
+        self.remote_host().is_some()
+    }
+
+    /// Where to ssh to for reaching ports on the engine's host, if that's another machine.
+    pub fn ssh_destination(&self) -> Option<String> {
+        let host = self.remote_host()?;
+        if matches!(host.as_str(), "localhost" | "127.0.0.1" | "::1") {
+            return None;
+        }
+        match self.socket() {
+            // ssh takes the same `ssh://[user@]host[:port]` form.
+            Some(socket) if socket.starts_with("ssh://") => Some(socket),
+            _ => Some(host),
+        }
+    }
+
+    pub fn keep_id(&self) -> bool {
+        self.kind == EngineKind::Podman && self.keep_id.unwrap_or(true)
+    }
+
+    fn socket(&self) -> Option<String> {
+        if let Some(socket) = &self.socket {
+            return Some(socket.clone());
+        }
+        if let Ok(host) = std::env::var("DOCKER_HOST") {
+            return Some(host);
+        }
+        match self.kind {
+            EngineKind::Docker => None,
+            EngineKind::Podman => {
+                // Rootless podman listens in the user's runtime dir; rootful in /run.
+                let rootless = std::env::var_os("XDG_RUNTIME_DIR")
+                    .map(|dir| PathBuf::from(dir).join("podman/podman.sock"))
+                    .filter(|p| p.exists());
+                let path = rootless.unwrap_or_else(|| "/run/podman/podman.sock".into());
+                Some(path.to_string_lossy().into_owned())
+            }
+        }
+    }
+
+    /// Connect to the engine's API.
+    ///
+    /// For `ssh://` hosts, this spawns an ssh tunnel to the remote socket, which is killed when the
+    /// returned `Child` is dropped.
+    pub async fn connect(&self) -> eyre::Result<(Docker, Option<Child>)> {
+        let (docker, tunnel) = match self.socket() {
+            Some(socket) if socket.starts_with("ssh://") => {
+                let (path, tunnel) = ssh_tunnel(&socket).await?;
+                let docker = Docker::connect_with_host(&format!("unix://{}", path.display()));
+                (docker, Some(tunnel))
+            }
+            Some(socket) if socket.contains("://") => (Docker::connect_with_host(&socket), None),
+            Some(path) => (Docker::connect_with_host(&format!("unix://{path}")), None),
+            None => (Docker::connect_with_local_defaults(), None),
+        };
+        let docker = docker.wrap_err_with(|| format!("failed to connect to {}", self.binary()))?;
+        Ok((docker, tunnel))
+    }
+}
+
+/// Where to put sockets for ssh tunnels.
+pub fn runtime_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from)
+}
+
+/// Forward a local unix socket to the docker socket on an `ssh://` host.
+async fn ssh_tunnel(destination: &str) -> eyre::Result<(PathBuf, Child)> {
+    let path = runtime_dir().join(format!("dc-ssh-{}.sock", std::process::id()));
+    let forward = format!("{}:/var/run/docker.sock", path.display());
+
+    let mut child = Command::new("ssh")
+        .args(["-nNT", "-o", "ExitOnForwardFailure=yes"])
+        .args(["-o", "StreamLocalBindUnlink=yes"])
+        .args(["-L", &forward, destination])
+        .kill_on_drop(true)
+        .spawn()
+        .wrap_err("failed to spawn ssh")?;
+
+    for _ in 0..200 {
+        if path.exists() {
+            return Ok((path, child));
+        }
+        if let Some(status) = child.try_wait()? {
+            return Err(eyre!("ssh tunnel to {destination} exited with {status}"));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    Err(eyre!("timed out waiting for ssh tunnel to {destination}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine(socket: &str) -> Engine {
+        Engine {
+            socket: Some(socket.into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn remote_host() {
+        assert_eq!(
+            engine("ssh://me@buildbox").remote_host().as_deref(),
+            Some("buildbox")
+        );
+        assert_eq!(
+            engine("tcp://10.0.0.5:2375").remote_host().as_deref(),
+            Some("10.0.0.5")
+        );
+        assert_eq!(
+            engine("tcp://[fd00::1]:2375").remote_host().as_deref(),
+            Some("fd00::1")
+        );
+        assert_eq!(engine("unix:///var/run/docker.sock").remote_host(), None);
+        assert_eq!(engine("/var/run/docker.sock").remote_host(), None);
+    }
+
+    #[test]
+    fn is_remote() {
+        assert!(engine("ssh://buildbox:22").is_remote());
+        assert!(engine("tcp://127.0.0.1:2375").is_remote());
+        assert!(!engine("unix:///var/run/docker.sock").is_remote());
+    }
+
+    #[test]
+    fn ssh_destination() {
+        assert_eq!(
+            engine("ssh://me@buildbox:22").ssh_destination().as_deref(),
+            Some("ssh://me@buildbox:22")
+        );
+        assert_eq!(
+            engine("tcp://buildbox:2375").ssh_destination().as_deref(),
+            Some("buildbox")
+        );
+        assert_eq!(engine("tcp://127.0.0.1:2375").ssh_destination(), None);
+        assert_eq!(
+            engine("unix:///var/run/docker.sock").ssh_destination(),
+            None
+        );
+    }
+
+    #[test]
+    fn compose_command() {
+        let mut e = engine("tcp://buildbox:2375");
+        e.context = Some("buildbox".into());
+        assert_eq!(
+            e.compose_command(),
+            ["docker", "--context", "buildbox", "compose"]
+        );
+    }
+}
@@ -7,15 +7,45 @@ pub mod complete;
 pub mod config;
 pub mod devcontainer;
 pub mod docker;
+pub mod engine;
 pub mod run;
 pub mod subscriber;
+pub mod trash;
 pub mod workspace;
 pub mod worktree;
 
 #[cfg(test)]
-mod test {
-    // We need at least 1 test to make cargo-nextest happy. Remove when we have
-    // real tests.
-    #[test]
-    fn test() {}
+pub(crate) mod test {
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    /// A temporary directory holding a new git repository, on `main` with no commits yet, at the
+    /// returned path, and a function that runs git there, panicking if it fails.
+    pub(crate) fn test_repo() -> (TempDir, PathBuf, impl Fn(&[&str])) {
+        let dir = tempfile::Builder::new()
+            .prefix("dc-test-")
+            .tempdir()
+            .unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir(&repo).unwrap();
+        let git = {
+            let repo = repo.clone();
+            move |args: &[&str]| git_in(&repo, args)
+        };
+        git(&["init", "-qb", "main"]);
+        (dir, repo, git)
+    }
+
+    fn git_in(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=dc", "-c", "user.email=dc@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?}");
+    }
 }
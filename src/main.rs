@@ -1,9 +1,14 @@
 #![forbid(unsafe_code)]
 
+use std::io::Write;
+
 use clap::{CommandFactory, Parser};
 use clap_complete::CompleteEnv;
 use color_eyre::config::HookBuilder;
-use dc::{self, cli::Cli};
+use dc::{
+    self,
+    cli::{Cli, ExitCode},
+};
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> eyre::Result<()> {
@@ -22,5 +27,11 @@ async fn main() -> eyre::Result<()> {
             std::process::exit(e.exit_code());
         }
     };
-    cli.run().await
+    match cli.run().await {
+        Err(e) if let Some(&ExitCode(code)) = e.downcast_ref() => {
+            std::io::stdout().flush()?;
+            std::process::exit(code);
+        }
+        result => result,
+    }
 }
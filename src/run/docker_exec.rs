@@ -3,10 +3,12 @@ use std::path::Path;
 
 use indexmap::IndexMap;
 
+use crate::engine::Engine;
 use crate::run;
 use crate::run::cmd::Cmd;
 
 pub struct DockerExec<'a> {
+    pub engine: &'a Engine,
     pub name: &'a str,
     pub container: &'a str,
     pub cmd: &'a Cmd,
@@ -48,7 +50,8 @@ impl run::Runnable for DockerExec<'_> {
         args.push(self.container);
         args.extend(self.cmd.as_args());
 
-        let full_argv: Vec<&str> = std::iter::once("docker").chain(args).collect();
+        let cli = self.engine.cli();
+        let full_argv: Vec<&str> = cli.iter().map(String::as_str).chain(args).collect();
         super::run_cmd(&full_argv, None).await
     }
 }
@@ -0,0 +1,461 @@
+//! Destroyed workspaces, kept for a while so that they can be restored.
+//!
+//! An entry records where the workspace was and what it had checked out. Its uncommitted changes,
+//! including untracked files, are kept as a commit at `refs/dc/trash/<entry>`, and its volumes are
+//! copied to volumes labeled `dev.dc.trash=<entry>`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use bollard::query_parameters::RemoveVolumeOptions;
+use eyre::{WrapErr, eyre};
+use futures::future::try_join_all;
+use jiff::{SignedDuration, Timestamp};
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+use crate::cli::State;
+use crate::cli::copy::do_copy_volume;
+use crate::docker::DockerClient;
+use crate::run::run_cmd;
+
+/// A destroyed workspace in the trash.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    /// The entry's name: the workspace's, and when it was destroyed.
+    pub name: String,
+    pub workspace: String,
+    /// Where the workspace's worktree was.
+    pub path: PathBuf,
+    /// Whether its worktree was removed, so restoring should recreate it. Not so for the root
+    /// workspace.
+    pub worktree: bool,
+    /// The branch it had checked out, unless HEAD was detached.
+    pub branch: Option<String>,
+    /// The name it was created with, if that isn't its directory's, from `worktree::set_name`.
+    #[serde(default)]
+    pub created_name: Option<String>,
+    /// Why its worktree was locked, if it was. The reason may be empty.
+    #[serde(default)]
+    pub locked: Option<String>,
+    /// The commit HEAD was at.
+    pub head: Option<String>,
+    /// The commit of its uncommitted changes, if it had any.
+    pub snapshot: Option<String>,
+    pub volumes: Vec<TrashedVolume>,
+    /// When it was destroyed, in seconds since the epoch.
+    pub deleted: i64,
+}
+
+/// A volume of a destroyed workspace, and the volume its contents were copied to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashedVolume {
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    pub copy: String,
+}
+
+impl Entry {
+    /// Put the workspace at `path` in the trash, before it's destroyed. Its containers should be
+    /// stopped, so its volumes are consistent.
+    pub async fn put(
+        docker: &DockerClient,
+        project: &str,
+        repo_path: &Path,
+        path: &Path,
+        compose_name: &str,
+        worktree: bool,
+    ) -> eyre::Result<Entry> {
+        let workspace = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let deleted = Timestamp::now();
+        let name = format!("{workspace}-{}", deleted.strftime("%Y%m%d-%H%M%S"));
+
+        let (branch, head, snapshot) = if worktree {
+            let head = git_output(&["rev-parse", "--verify", "-q", "HEAD"], path, &[])
+                .await
+                .ok();
+            let snapshot = match &head {
+                Some(head) => snapshot(path, &name, head).await?,
+                None => None,
+            };
+            (crate::worktree::branch(path)?, head, snapshot)
+        } else {
+            (None, None, None)
+        };
+        let (created_name, locked) = if worktree {
+            let worktrees = crate::worktree::list(repo_path).await?;
+            let wt = worktrees.into_iter().find(|wt| wt.path == path);
+            wt.map_or((None, None), |wt| (wt.name, wt.locked))
+        } else {
+            (None, None)
+        };
+
+        let volumes = docker.project_volumes(compose_name).await?;
+        let copies = volumes.into_iter().map(async |volume| {
+            let copy = format!("dc-trash_{name}_{}", volume.name);
+            let labels = HashMap::from([
+                ("dev.dc.trash".to_string(), name.clone()),
+                ("dev.dc.project".to_string(), project.to_string()),
+            ]);
+            do_copy_volume(&docker.docker, &volume.name, &copy, labels).await?;
+            eyre::Ok(TrashedVolume {
+                name: volume.name,
+                labels: volume.labels,
+                copy,
+            })
+        });
+        let volumes = try_join_all(copies).await?;
+
+        let entry = Entry {
+            name,
+            workspace,
+            path: path.to_path_buf(),
+            worktree,
+            branch,
+            created_name,
+            locked,
+            head,
+            snapshot,
+            volumes,
+            deleted: deleted.as_second(),
+        };
+        entry.save(project)?;
+        Ok(entry)
+    }
+
+    /// The project's entries, oldest first.
+    pub fn list(project: &str) -> eyre::Result<Vec<Entry>> {
+        let dir = dir(project)?;
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = Vec::new();
+        for file in std::fs::read_dir(&dir)? {
+            let path = file?.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            let contents = std::fs::read_to_string(&path)?;
+            let entry: Entry = serde_json::from_str(&contents)
+                .wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+            entries.push(entry);
+        }
+        entries.sort_by_key(|e| e.deleted);
+        Ok(entries)
+    }
+
+    /// The entry called `name`, or else the latest of the workspace called `name`.
+    pub fn find(project: &str, name: &str) -> eyre::Result<Entry> {
+        let mut entries = Entry::list(project)?;
+        let i = entries
+            .iter()
+            .position(|e| e.name == name)
+            .or_else(|| entries.iter().rposition(|e| e.workspace == name))
+            .ok_or_else(|| eyre!("nothing named '{name}' in the trash"))?;
+        Ok(entries.swap_remove(i))
+    }
+
+    /// When the entry expires, after being kept for `days`.
+    pub fn expires(&self, days: u32) -> Timestamp {
+        let deleted = Timestamp::from_second(self.deleted).unwrap_or_default();
+        deleted
+            .saturating_add(SignedDuration::from_hours(24 * i64::from(days)))
+            .unwrap_or(Timestamp::MAX)
+    }
+
+    fn save(&self, project: &str) -> eyre::Result<()> {
+        let dir = dir(project)?;
+        std::fs::create_dir_all(&dir)
+            .wrap_err_with(|| format!("failed to create {}", dir.display()))?;
+        let path = dir.join(format!("{}.json", self.name));
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .wrap_err_with(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Recreate the workspace's worktree, with its uncommitted changes, name and lock, and its
+    /// volumes, then delete the entry.
+    pub async fn restore(
+        self,
+        docker: &DockerClient,
+        project: &str,
+        repo_path: &Path,
+    ) -> eyre::Result<()> {
+        if self.worktree && self.path.exists() {
+            return Err(eyre!("{} already exists", self.path.display()));
+        }
+        let existing = docker.project_volumes(&compose_name(&self)).await?;
+        if let Some(v) = existing
+            .iter()
+            .find(|v| self.volumes.iter().any(|t| t.name == v.name))
+        {
+            return Err(eyre!(
+                "volume {} already exists; destroy the workspace first",
+                v.name
+            ));
+        }
+
+        if self.worktree {
+            self.restore_worktree(repo_path).await?;
+        }
+        let copies = self
+            .volumes
+            .iter()
+            .map(|v| do_copy_volume(&docker.docker, &v.copy, &v.name, v.labels.clone()));
+        try_join_all(copies).await?;
+
+        self.remove(docker, project, repo_path).await
+    }
+
+    async fn restore_worktree(&self, repo_path: &Path) -> eyre::Result<()> {
+        let head = self
+            .head
+            .as_deref()
+            .ok_or_else(|| eyre!("{} had no commits", self.workspace))?;
+        let path = self.path.to_string_lossy();
+        let mut args = vec!["git", "worktree", "add"];
+        match &self.branch {
+            Some(branch) if crate::worktree::branches(repo_path, false)?.contains(branch) => {
+                args.extend([&*path, branch]);
+            }
+            // The branch was deleted along with the workspace.
+            Some(branch) => args.extend(["-b", branch, &path, head]),
+            None => args.extend(["--detach", &path, head]),
+        }
+        run_cmd(&args, Some(repo_path)).await?;
+
+        if let Some(snapshot) = &self.snapshot {
+            // This also deletes tracked files that were deleted in the snapshot.
+            let source = format!("--source={snapshot}");
+            let args = ["git", "restore", &source, "--worktree", "--", "."];
+            run_cmd(&args, Some(&self.path)).await?;
+        }
+        if let Some(name) = &self.created_name {
+            crate::worktree::set_name(repo_path, &self.workspace, Some(name)).await?;
+        }
+        if let Some(reason) = &self.locked {
+            let reason = Some(reason.as_str()).filter(|r| !r.is_empty());
+            crate::worktree::lock(repo_path, &self.path, reason).await?;
+        }
+        Ok(())
+    }
+
+    /// Delete the entry, along with its snapshot and copied volumes.
+    pub async fn remove(
+        self,
+        docker: &DockerClient,
+        project: &str,
+        repo_path: &Path,
+    ) -> eyre::Result<()> {
+        for v in &self.volumes {
+            let options = RemoveVolumeOptions { force: true };
+            if let Err(e) = docker.docker.remove_volume(&v.copy, Some(options)).await
+                && !matches!(
+                    e,
+                    bollard::errors::Error::DockerResponseServerError {
+                        status_code: 404,
+                        ..
+                    }
+                )
+            {
+                return Err(e).wrap_err_with(|| format!("failed to remove volume {}", v.copy));
+            }
+        }
+        if self.snapshot.is_some() {
+            let trash_ref = trash_ref(&self.name);
+            run_cmd(&["git", "update-ref", "-d", &trash_ref], Some(repo_path)).await?;
+        }
+        let path = dir(project)?.join(format!("{}.json", self.name));
+        std::fs::remove_file(&path).wrap_err_with(|| format!("failed to remove {}", path.display()))
+    }
+}
+
+/// Delete the project's entries that were kept for longer than its `trash_days`.
+pub async fn purge(state: &State) -> eyre::Result<()> {
+    let now = Timestamp::now();
+    for entry in Entry::list(&state.project_name)? {
+        if entry.expires(state.project.trash_days) <= now {
+            entry
+                .remove(&state.docker, &state.project_name, &state.project.path)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Where the project's entries are recorded.
+fn dir(project: &str) -> eyre::Result<PathBuf> {
+    let dirs = directories::ProjectDirs::from("", "", "dc")
+        .ok_or_else(|| eyre!("could not determine data directory"))?;
+    Ok(dirs.data_dir().join("trash").join(project))
+}
+
+fn trash_ref(name: &str) -> String {
+    format!("refs/dc/trash/{name}")
+}
+
+fn compose_name(entry: &Entry) -> String {
+    crate::cli::up::compose_project_name(&entry.path)
+}
+
+/// Commit the uncommitted changes of the worktree at `path`, including untracked files, on top of
+/// `head`, keeping it at the entry's ref. `None` if there are none.
+///
+/// Ignored files, such as `.env`, are included too, unless git ignores their whole directory, as
+/// for build output like `target/`.
+///
+/// This uses a copy of the worktree's index, so it's left untouched.
+async fn snapshot(path: &Path, name: &str, head: &str) -> eyre::Result<Option<String>> {
+    let index = git_output(&["rev-parse", "--git-path", "index"], path, &[]).await?;
+    let index = path.join(index);
+    let tmp_index = std::env::temp_dir().join(format!("dc-trash-{name}.index"));
+    if index.exists() {
+        std::fs::copy(&index, &tmp_index)
+            .wrap_err_with(|| format!("failed to copy {}", index.display()))?;
+    }
+    let tmp_index_str = tmp_index.to_string_lossy();
+    let env = [("GIT_INDEX_FILE", &*tmp_index_str)];
+
+    let result = async {
+        git_output(&["add", "-A"], path, &env).await?;
+        let args = ["ls-files", "--others", "--ignored", "--exclude-standard"];
+        let ignored = git_output(&[&args[..], &["--directory", "-z"]].concat(), path, &env).await?;
+        let files: Vec<&str> = ignored
+            .split('\0')
+            .filter(|f| !f.is_empty() && !f.ends_with('/'))
+            .collect();
+        if !files.is_empty() {
+            let args = ["--literal-pathspecs", "add", "--force", "--"];
+            git_output(&[&args[..], &files].concat(), path, &env).await?;
+        }
+        let tree = git_output(&["write-tree"], path, &env).await?;
+        let head_tree = git_output(&["rev-parse", &format!("{head}^{{tree}}")], path, &[]).await?;
+        if tree == head_tree {
+            return Ok(None);
+        }
+
+        let message = format!("dc trash: {name}");
+        let env = [
+            ("GIT_AUTHOR_NAME", "dc"),
+            ("GIT_AUTHOR_EMAIL", "dc@localhost"),
+            ("GIT_COMMITTER_NAME", "dc"),
+            ("GIT_COMMITTER_EMAIL", "dc@localhost"),
+        ];
+        let args = ["commit-tree", &tree, "-p", head, "-m", &message];
+        let commit = git_output(&args, path, &env).await?;
+        git_output(&["update-ref", &trash_ref(name), &commit], path, &[]).await?;
+        Ok(Some(commit))
+    }
+    .await;
+
+    let _ = std::fs::remove_file(&tmp_index);
+    result
+}
+
+/// Run git in `dir`, returning its trimmed stdout.
+async fn git_output(args: &[&str], dir: &Path, env: &[(&str, &str)]) -> eyre::Result<String> {
+    let out = Command::new("git")
+        .args(args)
+        .envs(env.iter().copied())
+        .current_dir(dir)
+        .output()
+        .await?;
+    if !out.status.success() {
+        return Err(eyre!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8(out.stdout)
+        .wrap_err_with(|| format!("git {} output is not valid UTF-8", args.join(" ")))?
+        .trim()
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::test_repo;
+
+    #[tokio::test]
+    async fn snapshot_uncommitted_changes() {
+        let (_tmp, dir, git) = test_repo();
+
+        std::fs::write(dir.join(".gitignore"), "target/\n.env\n").unwrap();
+        std::fs::write(dir.join("a"), "a").unwrap();
+        std::fs::write(dir.join("b"), "b").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-qm", "a"]);
+        let head = git_output(&["rev-parse", "HEAD"], &dir, &[]).await.unwrap();
+        assert_eq!(snapshot(&dir, "clean", &head).await.unwrap(), None);
+
+        std::fs::write(dir.join("a"), "changed").unwrap();
+        std::fs::remove_file(dir.join("b")).unwrap();
+        std::fs::write(dir.join("new"), "new").unwrap();
+        std::fs::create_dir(dir.join("target")).unwrap();
+        std::fs::write(dir.join("target/out"), "").unwrap();
+        std::fs::write(dir.join(".env"), "SECRET=1").unwrap();
+        let commit = snapshot(&dir, "dirty", &head).await.unwrap().unwrap();
+
+        let files = git_output(&["ls-tree", "--name-only", &commit], &dir, &[])
+            .await
+            .unwrap();
+        assert_eq!(files, ".env\n.gitignore\na\nnew");
+        let kept = git_output(&["rev-parse", &trash_ref("dirty")], &dir, &[])
+            .await
+            .unwrap();
+        assert_eq!(kept, commit);
+        // The worktree's own index is untouched.
+        let status = git_output(&["status", "--porcelain"], &dir, &[])
+            .await
+            .unwrap();
+        assert_eq!(status, "M a\n D b\n?? new");
+    }
+
+    #[tokio::test]
+    async fn restore_worktree() {
+        let (tmp, repo, git) = test_repo();
+        let wt = tmp.path().join("feature");
+
+        std::fs::write(repo.join("a"), "a").unwrap();
+        std::fs::write(repo.join("b"), "b").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-qm", "a"]);
+        git(&["worktree", "add", "-qb", "feature", wt.to_str().unwrap()]);
+        std::fs::write(wt.join("a"), "changed").unwrap();
+        std::fs::remove_file(wt.join("b")).unwrap();
+        std::fs::write(wt.join("new"), "new").unwrap();
+
+        let head = git_output(&["rev-parse", "HEAD"], &wt, &[]).await.unwrap();
+        let snapshot = snapshot(&wt, "feature-1", &head).await.unwrap();
+        git(&["worktree", "remove", "--force", wt.to_str().unwrap()]);
+        git(&["branch", "-D", "feature"]);
+
+        let entry = Entry {
+            name: "feature-1".into(),
+            workspace: "feature".into(),
+            path: wt.clone(),
+            worktree: true,
+            branch: Some("feature".into()),
+            created_name: Some("user/feature".into()),
+            locked: Some("keep".into()),
+            head: Some(head),
+            snapshot,
+            volumes: Vec::new(),
+            deleted: 0,
+        };
+        entry.restore_worktree(&repo).await.unwrap();
+
+        assert_eq!(crate::worktree::branch(&wt).unwrap().unwrap(), "feature");
+        assert_eq!(std::fs::read_to_string(wt.join("a")).unwrap(), "changed");
+        assert!(!wt.join("b").exists());
+        assert_eq!(std::fs::read_to_string(wt.join("new")).unwrap(), "new");
+        let worktrees = crate::worktree::list(&repo).await.unwrap();
+        let restored = worktrees.iter().find(|w| w.path == wt).unwrap();
+        assert_eq!(restored.name.as_deref(), Some("user/feature"));
+        assert_eq!(restored.locked.as_deref(), Some("keep"));
+    }
+}
@@ -1,16 +1,22 @@
+use std::borrow::Borrow;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use bollard::models::ContainerSummaryStateEnum;
 use eyre::eyre;
 use futures::future::try_join_all;
+use indexmap::IndexMap;
 
 use crate::cli::State;
 use crate::cli::up::compose_project_name;
-use crate::docker::{ContainerInfo, ExecSession, Stats};
-use crate::worktree;
+use crate::docker::{ContainerInfo, DockerClient, ExecSession, Stats};
+use crate::workspace::git_status::{GitStatus, base_branch};
+use crate::worktree::{self, Worktree};
 
 pub mod git_status;
+pub mod live;
+pub mod query;
+pub mod record;
 pub mod table;
 
 #[derive(Debug)]
@@ -20,12 +26,18 @@ pub struct Workspace {
     pub root: bool,
     pub compose_project_name: String,
     pub containers: Vec<ContainerInfo>,
-    pub git_status: git_status::GitStatus,
+    pub git_status: GitStatus,
     pub execs: Vec<ExecSession>,
     pub stats: Stats,
+    /// Total size of the workspace's named volumes. Only collected with detailed stats.
+    pub volume_size: Option<u64>,
     pub fwd_ports: Vec<u16>,
     pub docker_ports: Vec<u16>,
     pub dc_managed: bool,
+    /// Whether this workspace uses the root workspace's shared services.
+    pub uses_shared: bool,
+    /// The workspace's worktree, if it's one of the project's.
+    pub worktree: Option<Worktree>,
 }
 
 impl Workspace {
@@ -43,26 +55,146 @@ impl Workspace {
                 .find(|g| g.path.file_name().is_some_and(|f| f == name))
                 .ok_or_else(|| eyre!("no workspace found for name {name}"))?
         };
-        group.into_workspace(state, &fwd_ports).await
+        let base = base_branch(&state.project);
+        group
+            .into_workspace(
+                &state.docker,
+                &state.project.path,
+                &base,
+                &fwd_ports,
+                None,
+                None,
+            )
+            .await
     }
 
     pub async fn list(state: &State) -> eyre::Result<Vec<Workspace>> {
+        Self::list_with_git(state, false, &HashMap::new()).await
+    }
+
+    /// Like `list`, but with detailed resource stats, which take longer to collect.
+    pub async fn list_detailed(state: &State) -> eyre::Result<Vec<Workspace>> {
+        Self::list_with_git(state, true, &HashMap::new()).await
+    }
+
+    /// List workspaces, reusing the git statuses in `git`, by worktree path, rather than fetching
+    /// them.
+    pub async fn list_with_git(
+        state: &State,
+        detailed: bool,
+        git: &HashMap<PathBuf, GitStatus>,
+    ) -> eyre::Result<Vec<Workspace>> {
         let (groups, fwd_ports) = ContainerGroup::list(state).await?;
-        let futures = groups
-            .into_iter()
-            .map(|g| g.into_workspace(state, &fwd_ports));
+        let volume_sizes = if detailed {
+            Some(state.docker.volume_sizes().await?)
+        } else {
+            None
+        };
+        let base = base_branch(&state.project);
+        let futures = groups.into_iter().map(|g| {
+            let git_status = git.get(&g.path).cloned();
+            g.into_workspace(
+                &state.docker,
+                &state.project.path,
+                &base,
+                &fwd_ports,
+                volume_sizes.as_ref(),
+                git_status,
+            )
+        });
 
         try_join_all(futures).await
     }
 
+    /// Workspaces of dc-managed containers whose project isn't in `configured`, grouped by their
+    /// project's name.
+    pub async fn unconfigured(
+        docker: &DockerClient,
+        configured: &[String],
+        detailed: bool,
+    ) -> eyre::Result<IndexMap<String, Vec<Workspace>>> {
+        let containers = docker.container_info().await?;
+        let volume_sizes = if detailed {
+            Some(docker.volume_sizes().await?)
+        } else {
+            None
+        };
+
+        let mut groups: IndexMap<(String, PathBuf), ContainerGroup> = IndexMap::new();
+        for c in containers {
+            let Some(project) = c.dc_project.clone() else {
+                continue;
+            };
+            if configured.contains(&project) {
+                continue;
+            }
+            groups
+                .entry((project, c.local_folder.clone()))
+                .or_insert_with(|| ContainerGroup {
+                    path: c.local_folder.clone(),
+                    containers: Vec::new(),
+                    worktree: None,
+                })
+                .containers
+                .push(c);
+        }
+
+        let no_fwd_ports = HashMap::new();
+        let futures = groups.into_iter().map(async |((project, _), group)| {
+            // We don't know the project's root, so nothing is treated as one, nor its base branch.
+            let ws = group
+                .into_workspace(
+                    docker,
+                    Path::new(""),
+                    "",
+                    &no_fwd_ports,
+                    volume_sizes.as_ref(),
+                    None,
+                )
+                .await?;
+            eyre::Ok((project, ws))
+        });
+
+        let mut result: IndexMap<String, Vec<Workspace>> = IndexMap::new();
+        for (project, ws) in try_join_all(futures).await? {
+            result.entry(project).or_default().push(ws);
+        }
+        result.sort_keys();
+        Ok(result)
+    }
+
+    /// Re-fetch the exec sessions and resource use of the workspace's containers.
+    ///
+    /// With `volume_sizes`, from `DockerClient::volume_sizes`, detailed stats are fetched.
+    pub async fn refresh_usage(
+        &mut self,
+        docker: &DockerClient,
+        volume_sizes: Option<&HashMap<String, u64>>,
+    ) -> eyre::Result<()> {
+        let (execs, stats) = usage(docker, &self.containers, volume_sizes.is_some()).await?;
+        self.execs = execs;
+        self.stats = stats;
+        self.volume_size = volume_sizes.map(|v| {
+            v.get(&self.compose_project_name)
+                .copied()
+                .unwrap_or_default()
+        });
+        Ok(())
+    }
+
+    /// The most active state of any container, e.g. running if any are.
     pub fn status(&self) -> ContainerSummaryStateEnum {
         self.containers
             .iter()
             .map(|c| c.state)
-            .max()
+            .max_by_key(|s| query::activity(*s))
             .unwrap_or(ContainerSummaryStateEnum::EMPTY)
     }
 
+    pub fn is_running(&self) -> bool {
+        self.status() == ContainerSummaryStateEnum::RUNNING
+    }
+
     pub fn created(&self) -> Option<i64> {
         self.containers.iter().filter_map(|c| c.created).min()
     }
@@ -71,25 +203,70 @@ impl Workspace {
         self.git_status.is_dirty()
     }
 
-    pub fn service_container_id(&self) -> eyre::Result<&str> {
-        // FIXME: We need to find the correct service container.
-        Ok(&self
-            .containers
-            .first()
-            .ok_or_else(|| eyre!("no containers for workspace"))?
-            .id)
+    /// Whether the workspace is protected from `dc destroy` and `dc prune` by `dc lock`.
+    pub fn is_locked(&self) -> bool {
+        self.worktree.as_ref().is_some_and(|wt| wt.locked.is_some())
+    }
+
+    /// Sort for display: the root workspace first, then by name.
+    pub fn sort<W: Borrow<Workspace>>(workspaces: &mut [W]) {
+        workspaces.sort_by(|a, b| {
+            let (a, b) = (a.borrow(), b.borrow());
+            b.root.cmp(&a.root).then_with(|| a.name.cmp(&b.name))
+        });
+    }
+
+    /// The container running the devcontainer's primary `service`.
+    pub fn service_container(&self, service: &str) -> eyre::Result<&ContainerInfo> {
+        self.containers
+            .iter()
+            .find(|c| c.service.as_deref() == Some(service))
+            // Containers not started by compose have no service, but we labeled the primary one.
+            .or_else(|| self.containers.iter().find(|c| c.primary))
+            .ok_or_else(|| {
+                eyre!(
+                    "no container for service {service} in workspace {}",
+                    self.name
+                )
+            })
+    }
+
+    pub fn service_container_id(&self, service: &str) -> eyre::Result<&str> {
+        Ok(&self.service_container(service)?.id)
     }
 }
 
+/// The exec sessions and summed resource use of `containers`.
+async fn usage(
+    docker: &DockerClient,
+    containers: &[ContainerInfo],
+    detailed: bool,
+) -> eyre::Result<(Vec<ExecSession>, Stats)> {
+    let execs_futures = try_join_all(containers.iter().map(|c| docker.execs(&c.id)));
+    let stats_futures = try_join_all(containers.iter().map(|c| docker.stats(&c.id, detailed)));
+    let (execs, stats) = tokio::try_join!(execs_futures, stats_futures)?;
+    Ok((
+        execs.into_iter().flatten().collect(),
+        stats.into_iter().sum(),
+    ))
+}
+
+/// The base branch to check whether a workspace is merged into, if any. The root workspace is
+/// usually on it.
+fn merge_target(root: bool, base: &str) -> Option<&str> {
+    (!root && !base.is_empty()).then_some(base)
+}
+
 // Group of containers by worktree path
 struct ContainerGroup {
     path: PathBuf,
     containers: Vec<ContainerInfo>,
+    worktree: Option<Worktree>,
 }
 
 impl ContainerGroup {
     async fn list(state: &State) -> eyre::Result<(Vec<Self>, HashMap<String, Vec<u16>>)> {
-        let worktree_paths = worktree::list(&state.project.path).await?;
+        let worktrees = worktree::list(&state.project.path).await?;
         let (containers, fwd_ports) = tokio::try_join!(
             state.docker.container_info(),
             state.docker.forwarded_ports(&state.project_name),
@@ -104,7 +281,7 @@ impl ContainerGroup {
                 // This is a dc-managed container for a different project.
                 continue;
             }
-            if c.dc_project.is_none() && !worktree_paths.contains(&c.local_folder) {
+            if c.dc_project.is_none() && !worktrees.iter().any(|wt| wt.path == c.local_folder) {
                 // This is not a devcontainer for any of our worktrees.
                 continue;
             }
@@ -113,37 +290,46 @@ impl ContainerGroup {
                 .or_insert_with(|| ContainerGroup {
                     path: c.local_folder.clone(),
                     containers: Vec::new(),
+                    worktree: None,
                 });
             group.containers.push(c);
         }
 
         // Ensure we have an entry for all of our worktrees.
-        for path in worktree_paths {
-            groups
-                .entry(path.clone())
+        for wt in worktrees {
+            let group = groups
+                .entry(wt.path.clone())
                 .or_insert_with(|| ContainerGroup {
-                    path,
+                    path: wt.path.clone(),
                     containers: Vec::new(),
+                    worktree: None,
                 });
+            group.worktree = Some(wt);
         }
         Ok((groups.into_values().collect(), fwd_ports))
     }
 
+    /// Make a workspace of the group's containers, whose git status is `git_status` if given, or
+    /// else is fetched, checking whether it's merged into `base` unless that's empty.
     async fn into_workspace(
         self,
-        state: &State,
+        docker: &DockerClient,
+        root_path: &Path,
+        base: &str,
         fwd_ports: &HashMap<String, Vec<u16>>,
+        volume_sizes: Option<&HashMap<String, u64>>,
+        git_status: Option<GitStatus>,
     ) -> eyre::Result<Workspace> {
-        let git_future = git_status::GitStatus::fetch(&self.path);
-        let execs_futures = try_join_all(self.containers.iter().map(|c| state.docker.execs(&c.id)));
-        let stats_futures = try_join_all(self.containers.iter().map(|c| state.docker.stats(&c.id)));
-        let (git_status, execs, stats) =
-            tokio::try_join!(git_future, execs_futures, stats_futures)?;
-
-        let execs = execs.into_iter().flatten().collect();
-        let stats = stats.into_iter().sum();
+        let root = self.path == root_path;
+        let git_future = async {
+            match git_status {
+                Some(git_status) => Ok(git_status),
+                None => GitStatus::fetch(&self.path, merge_target(root, base)).await,
+            }
+        };
+        let usage_future = usage(docker, &self.containers, volume_sizes.is_some());
+        let (git_status, (execs, stats)) = tokio::try_join!(git_future, usage_future)?;
 
-        let root = self.path == state.project.path;
         let name = self
             .path
             .file_name()
@@ -151,6 +337,8 @@ impl ContainerGroup {
             .unwrap_or_default();
 
         let compose_project_name = compose_project_name(&self.path);
+        let volume_size =
+            volume_sizes.map(|v| v.get(&compose_project_name).copied().unwrap_or_default());
         let mut fwd_ports = fwd_ports
             .get(&compose_project_name)
             .cloned()
@@ -168,6 +356,7 @@ impl ContainerGroup {
         docker_ports.dedup();
 
         let dc_managed = self.containers.iter().any(|c| c.dc_project.is_some());
+        let uses_shared = self.containers.iter().any(|c| c.uses_shared);
 
         Ok(Workspace {
             compose_project_name,
@@ -178,9 +367,67 @@ impl ContainerGroup {
             git_status,
             execs,
             stats,
+            volume_size,
             fwd_ports,
             docker_ports,
             dc_managed,
+            uses_shared,
+            worktree: self.worktree,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(id: &str, service: Option<&str>, primary: bool) -> ContainerInfo {
+        ContainerInfo {
+            id: id.into(),
+            state: ContainerSummaryStateEnum::RUNNING,
+            local_folder: "/tmp/feature".into(),
+            dc_project: Some("best".into()),
+            created: None,
+            host_ports: Vec::new(),
+            profiles: Vec::new(),
+            compose_files: Vec::new(),
+            uses_shared: false,
+            service: service.map(Into::into),
+            primary,
+        }
+    }
+
+    fn workspace(containers: Vec<ContainerInfo>) -> Workspace {
+        Workspace {
+            path: "/tmp/feature".into(),
+            name: "feature".into(),
+            root: false,
+            compose_project_name: "feature_devcontainer".into(),
+            containers,
+            git_status: GitStatus::default(),
+            execs: Vec::new(),
+            stats: Stats::default(),
+            volume_size: None,
+            fwd_ports: Vec::new(),
+            docker_ports: Vec::new(),
+            dc_managed: true,
+            uses_shared: false,
+            worktree: None,
+        }
+    }
+
+    #[test]
+    fn service_container() {
+        let ws = workspace(vec![
+            container("db", Some("db"), false),
+            container("app", Some("app"), true),
+        ]);
+        assert_eq!(ws.service_container_id("app").unwrap(), "app");
+
+        // If the devcontainer's service was renamed since `dc up`, fall back to the one we labeled.
+        assert_eq!(ws.service_container_id("web").unwrap(), "app");
+
+        let ws = workspace(vec![container("db", Some("db"), false)]);
+        assert!(ws.service_container_id("app").is_err());
+    }
+}
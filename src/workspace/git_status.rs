@@ -1,9 +1,15 @@
+use std::collections::HashSet;
 use std::fmt;
 use std::path::Path;
+use std::time::SystemTime;
 
+use gix::bstr::BString;
 use owo_colors::OwoColorize;
+use serde::Serialize;
 
-#[derive(Debug, Default)]
+use crate::config::Project;
+
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct GitStatus {
     pub ahead: usize,
     pub behind: usize,
@@ -13,15 +19,48 @@ pub struct GitStatus {
     pub untracked: usize,
     pub conflicted: usize,
     pub renamed: usize,
+    /// The checked-out branch, unless HEAD is detached.
+    pub branch: Option<String>,
+    /// The branch's upstream, e.g. `origin/main`.
+    pub upstream: Option<String>,
+    pub last_commit: Option<LastCommit>,
+    /// Entries in the stash that were made on the checked-out branch. All worktrees share one
+    /// stash, so those made elsewhere aren't counted.
+    pub stashes: usize,
+    /// Whether HEAD is merged into the base branch, if we know.
+    pub merged: Option<Merged>,
+}
+
+/// Whether a worktree's changes have made it into the base branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Merged {
+    No,
+    /// HEAD is an ancestor of the base branch.
+    Yes,
+    /// HEAD is the base branch's commit, e.g. in a new workspace, so there's nothing to merge.
+    NoCommits,
+    /// HEAD's changes are in the base branch, but its commits aren't, e.g. after a squash merge.
+    Squashed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LastCommit {
+    pub subject: String,
+    /// Unix timestamp of when it was committed.
+    pub time: i64,
 }
 
 impl GitStatus {
-    pub async fn fetch(path: &Path) -> eyre::Result<Self> {
+    /// Fetch the status of the worktree at `path`, and whether it's merged into `base`, a revision
+    /// such as from `base_branch`.
+    pub async fn fetch(path: &Path, base: Option<&str>) -> eyre::Result<Self> {
         if !path.exists() {
             return Ok(Self::default());
         }
         let path = path.to_owned();
-        tokio::task::spawn_blocking(move || fetch_sync(&path)).await?
+        let base = base.map(ToOwned::to_owned);
+        tokio::task::spawn_blocking(move || fetch_sync(&path, base.as_deref())).await?
     }
 
     pub fn is_dirty(&self) -> bool {
@@ -30,13 +69,35 @@ impl GitStatus {
     }
 }
 
-fn fetch_sync(path: &Path) -> eyre::Result<GitStatus> {
+/// The branch to check whether workspaces are merged into: the project's configured one, the root
+/// workspace's, or the remote's default.
+pub fn base_branch(project: &Project) -> String {
+    if let Some(base) = &project.base_branch {
+        return base.clone();
+    }
+    gix::open(&project.path)
+        .ok()
+        .and_then(|repo| repo.head_name().ok().flatten())
+        .map(|name| name.shorten().to_string())
+        .unwrap_or_else(|| "origin/HEAD".into())
+}
+
+fn fetch_sync(path: &Path, base: Option<&str>) -> eyre::Result<GitStatus> {
     let repo = gix::open(path)?;
-    let mut gs = GitStatus::default();
+    let mut gs = GitStatus {
+        branch: repo.head_name()?.map(|name| name.shorten().to_string()),
+        last_commit: last_commit(&repo).ok(),
+        stashes: stashes(&repo).unwrap_or(0),
+        merged: base.and_then(|base| merged(&repo, base).ok()),
+        ..Default::default()
+    };
 
-    let (ahead, behind) = ahead_behind(&repo).unwrap_or((0, 0));
-    gs.ahead = ahead;
-    gs.behind = behind;
+    if let Ok(upstream) = upstream(&repo) {
+        gs.upstream = Some(upstream.shorten().to_string());
+        let (ahead, behind) = ahead_behind(&repo, &upstream).unwrap_or((0, 0));
+        gs.ahead = ahead;
+        gs.behind = behind;
+    }
 
     let iter = repo.status(gix::progress::Discard)?.into_iter(Vec::new())?;
 
@@ -70,23 +131,129 @@ fn fetch_sync(path: &Path) -> eyre::Result<GitStatus> {
     Ok(gs)
 }
 
-fn ahead_behind(repo: &gix::Repository) -> eyre::Result<(usize, usize)> {
-    let head = repo.head()?;
-    let head_id = head
-        .id()
-        .ok_or_else(|| eyre::eyre!("unborn HEAD"))?
-        .detach();
+/// A cheap summary of a worktree's files and git metadata, for telling when its `GitStatus` may
+/// have changed without computing it.
+///
+/// This is the newest modification time and number of entries among the files git updates on
+/// commits, checkouts, and fetches, and the worktree's non-ignored top-level files and
+/// directories. A directory's time changes when entries are added to it or removed, as editors
+/// that save by renaming do, but not when a file below the top level is edited in place, so live
+/// views also recompute every `GitStatus` now and then.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    newest: Option<SystemTime>,
+    entries: usize,
+}
+
+impl Fingerprint {
+    pub async fn fetch(path: &Path) -> eyre::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let path = path.to_owned();
+        tokio::task::spawn_blocking(move || fingerprint_sync(&path)).await?
+    }
+
+    fn add(&mut self, meta: &std::fs::Metadata) {
+        self.entries += 1;
+        if let Ok(modified) = meta.modified() {
+            self.newest = self.newest.max(Some(modified));
+        }
+    }
 
-    let referent = head
+    fn add_path(&mut self, path: &Path) {
+        if let Ok(meta) = path.symlink_metadata() {
+            self.add(&meta);
+        }
+    }
+
+    /// Add `dir` and everything under it.
+    fn add_tree(&mut self, dir: &Path) -> eyre::Result<()> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Ok(());
+        };
+        self.add_path(dir);
+        for entry in entries {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                self.add_tree(&entry.path())?;
+            } else {
+                self.add(&meta);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add the worktree at `root`, and its entries that `excludes` doesn't ignore, but nothing
+    /// below them.
+    fn add_top_level(
+        &mut self,
+        root: &Path,
+        excludes: &mut gix::AttributeStack<'_>,
+    ) -> eyre::Result<()> {
+        self.add_path(root);
+        for entry in std::fs::read_dir(root)? {
+            let entry = entry?;
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            let meta = entry.metadata()?;
+            let mode = meta.is_dir().then_some(gix::index::entry::Mode::DIR);
+            if !excludes
+                .at_path(Path::new(&entry.file_name()), mode)?
+                .is_excluded()
+            {
+                self.add(&meta);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn fingerprint_sync(path: &Path) -> eyre::Result<Fingerprint> {
+    let repo = gix::open(path)?;
+    let mut fp = Fingerprint::default();
+
+    for dir in [repo.git_dir(), repo.common_dir()] {
+        for file in ["HEAD", "index", "FETCH_HEAD", "packed-refs"] {
+            fp.add_path(&dir.join(file));
+        }
+    }
+    // Loose refs change on commits and ref updates, including pushes.
+    fp.add_tree(&repo.common_dir().join("refs"))?;
+
+    // The index may not exist yet, in a new repository.
+    let index = repo.index_or_empty()?;
+    let source = gix::worktree::stack::state::ignore::Source::WorktreeThenIdMappingIfNotSkipped;
+    let mut excludes = repo.excludes(&index, None, source)?;
+    fp.add_top_level(path, &mut excludes)?;
+
+    Ok(fp)
+}
+
+/// The remote-tracking branch that HEAD's branch fetches from.
+fn upstream(repo: &gix::Repository) -> eyre::Result<gix::refs::FullName> {
+    let referent = repo
+        .head()?
         .try_into_referent()
         .ok_or_else(|| eyre::eyre!("detached HEAD"))?;
-    let tracking_ref_name = referent
+    let name = referent
         .remote_tracking_ref_name(gix::remote::Direction::Fetch)
         .ok_or_else(|| eyre::eyre!("no tracking branch"))??;
-    let tracking_id = repo
-        .find_reference(tracking_ref_name.as_ref())?
+    Ok(name.into_owned())
+}
+
+fn ahead_behind(
+    repo: &gix::Repository,
+    upstream: &gix::refs::FullName,
+) -> eyre::Result<(usize, usize)> {
+    let head_id = repo
+        .head()?
         .id()
+        .ok_or_else(|| eyre::eyre!("unborn HEAD"))?
         .detach();
+    let tracking_id = repo.find_reference(upstream.as_ref())?.id().detach();
 
     if head_id == tracking_id {
         return Ok((0, 0));
@@ -109,6 +276,146 @@ fn ahead_behind(repo: &gix::Repository) -> eyre::Result<(usize, usize)> {
     Ok((ahead, behind))
 }
 
+/// How many of the base branch's commits since it diverged from HEAD to look through for one that
+/// made the same changes as HEAD.
+const SQUASH_SEARCH_DEPTH: usize = 200;
+
+fn merged(repo: &gix::Repository, base: &str) -> eyre::Result<Merged> {
+    let head = repo.head_id()?.detach();
+    let base = repo.rev_parse_single(base)?.object()?.peel_to_commit()?.id;
+    if head == base {
+        return Ok(Merged::NoCommits);
+    }
+    // Like `git rev-list base..HEAD`.
+    let mut unmerged = repo.rev_walk([head]).with_hidden([base]).all()?;
+    if unmerged.next().transpose()?.is_none() {
+        return Ok(Merged::Yes);
+    }
+
+    let merge_base = repo.merge_base(head, base)?.detach();
+    let tree = |id| eyre::Ok(repo.find_commit(id)?.tree()?);
+    let head_changes = changes(repo, &tree(merge_base)?, &tree(head)?)?;
+    // Commits that change nothing can't have been squashed into anything.
+    if head_changes.is_empty() {
+        return Ok(Merged::No);
+    }
+
+    // If the base has the same version of every file that HEAD changed, it has HEAD's changes.
+    let differences: HashSet<BString> = changes(repo, &tree(head)?, &tree(base)?)?
+        .into_iter()
+        .map(|change| change.path)
+        .collect();
+    if !head_changes
+        .iter()
+        .any(|change| differences.contains(&change.path))
+    {
+        return Ok(Merged::Squashed);
+    }
+
+    // Those files may have changed since, so look for a commit that made the same changes.
+    let walk = repo.rev_walk([base]).with_hidden([merge_base]).all()?;
+    for info in walk.take(SQUASH_SEARCH_DEPTH) {
+        let info = info?;
+        let [parent] = info.parent_ids[..] else {
+            continue;
+        };
+        if changes(repo, &tree(parent)?, &tree(info.id)?)? == head_changes {
+            return Ok(Merged::Squashed);
+        }
+    }
+    Ok(Merged::No)
+}
+
+/// A file's blob ids before and after a change, where `None` means it didn't exist.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct FileChange {
+    path: BString,
+    old: Option<gix::ObjectId>,
+    new: Option<gix::ObjectId>,
+}
+
+/// The files changed from `old` to `new`, sorted by path.
+fn changes(
+    repo: &gix::Repository,
+    old: &gix::Tree<'_>,
+    new: &gix::Tree<'_>,
+) -> eyre::Result<Vec<FileChange>> {
+    use gix::object::tree::diff::ChangeDetached as Change;
+
+    let mut changes: Vec<_> = repo
+        .diff_tree_to_tree(old, new, gix::diff::Options::default())?
+        .into_iter()
+        .filter(|change| !change.entry_mode().is_tree())
+        .filter_map(|change| match change {
+            Change::Addition { location, id, .. } => Some(FileChange {
+                path: location,
+                old: None,
+                new: Some(id),
+            }),
+            Change::Deletion { location, id, .. } => Some(FileChange {
+                path: location,
+                old: Some(id),
+                new: None,
+            }),
+            Change::Modification {
+                location,
+                previous_id,
+                id,
+                ..
+            } => Some(FileChange {
+                path: location,
+                old: Some(previous_id),
+                new: Some(id),
+            }),
+            // Only reported when tracking renames, which we don't.
+            Change::Rewrite { .. } => None,
+        })
+        .collect();
+    changes.sort();
+    Ok(changes)
+}
+
+fn last_commit(repo: &gix::Repository) -> eyre::Result<LastCommit> {
+    let commit = repo.head_commit()?;
+    Ok(LastCommit {
+        subject: commit.message()?.summary().to_string(),
+        time: commit.time()?.seconds,
+    })
+}
+
+/// The stash entries made on HEAD's branch, which git names in each entry's message, e.g.
+/// `WIP on feature: 1234abc subject`. None for a detached HEAD, whose entries say `(no branch)`.
+fn stashes(repo: &gix::Repository) -> eyre::Result<usize> {
+    let Some(branch) = repo.head_name()? else {
+        return Ok(0);
+    };
+    let Some(stash) = repo.try_find_reference("refs/stash")? else {
+        return Ok(0);
+    };
+    let mut log = stash.log_iter();
+    let Some(lines) = log.all()? else {
+        return Ok(0);
+    };
+    let mut count = 0;
+    for line in lines {
+        if stash_branch(line?.message) == Some(branch.shorten()) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// The branch named in a stash entry's message.
+fn stash_branch(message: &gix::bstr::BStr) -> Option<&gix::bstr::BStr> {
+    use gix::bstr::ByteSlice;
+
+    let rest = message
+        .strip_prefix(b"WIP on ")
+        .or_else(|| message.strip_prefix(b"On "))?;
+    // Branch names can't contain a colon, so the first one ends it.
+    Some(rest[..rest.find_byte(b':')?].as_bstr())
+}
+
 impl fmt::Display for GitStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut s = String::new();
@@ -141,3 +448,127 @@ impl fmt::Display for GitStatus {
         write!(f, "{}", s.red())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::test_repo;
+
+    #[test]
+    fn fingerprint_ignores_ignored_files() {
+        let (_tmp, dir, _) = test_repo();
+        std::fs::write(dir.join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir(dir.join("target")).unwrap();
+        let before = fingerprint_sync(&dir).unwrap();
+
+        std::fs::write(dir.join("target/out"), "").unwrap();
+        assert_eq!(fingerprint_sync(&dir).unwrap(), before);
+
+        std::fs::write(dir.join("src.rs"), "").unwrap();
+        let top_level = fingerprint_sync(&dir).unwrap();
+        assert_ne!(top_level, before);
+
+        // Adding a file to a directory changes the directory's time.
+        std::fs::create_dir(dir.join("src")).unwrap();
+        let before = fingerprint_sync(&dir).unwrap();
+        assert_ne!(before, top_level);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        assert_ne!(fingerprint_sync(&dir).unwrap(), before);
+    }
+
+    #[test]
+    fn merged_branches() {
+        let (_tmp, dir, git) = test_repo();
+        let commit = |file: &str, contents: &str| {
+            std::fs::write(dir.join(file), contents).unwrap();
+            git(&["add", file]);
+            git(&["commit", "-qm", contents]);
+        };
+        let merged = || {
+            let repo = gix::open(&dir).unwrap();
+            merged(&repo, "main").unwrap()
+        };
+
+        commit("a", "a");
+        git(&["checkout", "-qb", "old"]);
+        assert_eq!(merged(), Merged::NoCommits);
+        commit("c", "c");
+        assert_eq!(merged(), Merged::No);
+        git(&["checkout", "-q", "main"]);
+        git(&["merge", "-q", "--ff-only", "old"]);
+        git(&["checkout", "-q", "old"]);
+        // Once fast-forwarded, it's where a new branch would be.
+        assert_eq!(merged(), Merged::NoCommits);
+        git(&["checkout", "-q", "main"]);
+        commit("d", "d");
+        git(&["checkout", "-q", "old"]);
+        assert_eq!(merged(), Merged::Yes);
+
+        git(&["checkout", "-qb", "empty", "main"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "empty"]);
+        assert_eq!(merged(), Merged::No);
+
+        git(&["checkout", "-qb", "feature", "main"]);
+        commit("b", "b1");
+        commit("b", "b2");
+        assert_eq!(merged(), Merged::No);
+
+        git(&["checkout", "-q", "main"]);
+        git(&["merge", "-q", "--squash", "feature"]);
+        git(&["commit", "-qm", "squashed"]);
+        git(&["checkout", "-q", "feature"]);
+        assert_eq!(merged(), Merged::Squashed);
+
+        // Even once the base has changed the files since.
+        git(&["checkout", "-q", "main"]);
+        commit("b", "b3");
+        git(&["checkout", "-q", "feature"]);
+        assert_eq!(merged(), Merged::Squashed);
+
+        git(&["checkout", "-q", "old"]);
+        assert_eq!(merged(), Merged::Yes);
+    }
+
+    #[test]
+    fn stashes_on_the_branch() {
+        let (tmp, dir, git) = test_repo();
+        std::fs::write(dir.join("a"), "a").unwrap();
+        git(&["add", "a"]);
+        git(&["commit", "-qm", "a"]);
+        let feature = tmp.path().join("feature");
+        git(&[
+            "worktree",
+            "add",
+            "-qb",
+            "feature",
+            feature.to_str().unwrap(),
+        ]);
+        let stashes = |path: &Path| stashes(&gix::open(path).unwrap()).unwrap();
+
+        std::fs::write(feature.join("a"), "b").unwrap();
+        git(&["-C", feature.to_str().unwrap(), "stash", "-q"]);
+        std::fs::write(feature.join("a"), "c").unwrap();
+        git(&[
+            "-C",
+            feature.to_str().unwrap(),
+            "stash",
+            "push",
+            "-qm",
+            "named",
+        ]);
+        std::fs::write(dir.join("a"), "d").unwrap();
+        git(&["stash", "-q"]);
+
+        assert_eq!(stashes(&feature), 2);
+        assert_eq!(stashes(&dir), 1);
+        git(&[
+            "-C",
+            feature.to_str().unwrap(),
+            "checkout",
+            "-q",
+            "--detach",
+        ]);
+        assert_eq!(stashes(&feature), 0);
+    }
+}
@@ -0,0 +1,135 @@
+//! A list of workspaces that is kept up to date cheaply, for live views like `dc list --watch` and
+//! `dc tui`.
+//!
+//! Rather than re-listing everything on every tick, we re-list containers only when told that one
+//! of ours changed, refresh resource use on a timer, and recompute git status for worktrees whose
+//! files changed, and now and then for all of them.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use bollard::models::EventMessage;
+use eyre::OptionExt;
+use futures::{Stream, StreamExt, future::try_join_all};
+
+use crate::{
+    cli::State,
+    workspace::{
+        Workspace,
+        git_status::{Fingerprint, GitStatus, base_branch},
+        merge_target,
+    },
+    worktree::{self, Worktree},
+};
+
+/// Events come in bursts, e.g. for each container in a compose project, so we wait this long for
+/// more before reporting a change.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A worktree's fingerprint misses edits below its top level, so we recompute every git status at
+/// least this often.
+const GIT_STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Given an `event` from `DockerClient::container_events`, wait out the rest of its burst.
+///
+/// This takes the first event, rather than waiting for it, so that callers can wait on
+/// `events.next()`, which is cancel-safe, in a `select!`.
+pub async fn settle(
+    event: Option<eyre::Result<EventMessage>>,
+    events: &mut (impl Stream<Item = eyre::Result<EventMessage>> + Unpin),
+) -> eyre::Result<()> {
+    event.ok_or_eyre("docker event stream ended")??;
+    while let Ok(Some(event)) = tokio::time::timeout(DEBOUNCE, events.next()).await {
+        event?;
+    }
+    Ok(())
+}
+
+pub struct LiveWorkspaces {
+    pub workspaces: Vec<Workspace>,
+    detailed: bool,
+    worktrees: Vec<Worktree>,
+    fingerprints: HashMap<PathBuf, Fingerprint>,
+    /// When every git status was last recomputed.
+    git_fetched: Instant,
+}
+
+impl LiveWorkspaces {
+    pub async fn new(state: &State, detailed: bool) -> eyre::Result<Self> {
+        // Fingerprint before listing, so that changes made while we list aren't missed.
+        let git_fetched = Instant::now();
+        let worktrees = worktree::list(&state.project.path).await?;
+        let futures = worktrees.iter().map(async |wt| {
+            let fingerprint = Fingerprint::fetch(&wt.path).await?;
+            eyre::Ok((wt.path.clone(), fingerprint))
+        });
+        let fingerprints = try_join_all(futures).await?.into_iter().collect();
+        let workspaces = Workspace::list_with_git(state, detailed, &HashMap::new()).await?;
+
+        Ok(LiveWorkspaces {
+            workspaces,
+            detailed,
+            worktrees,
+            fingerprints,
+            git_fetched,
+        })
+    }
+
+    /// List workspaces again, e.g. after a container event, keeping the git statuses we have.
+    pub async fn relist(&mut self, state: &State) -> eyre::Result<()> {
+        let git = self
+            .workspaces
+            .iter()
+            .map(|ws| (ws.path.clone(), ws.git_status.clone()))
+            .collect();
+        self.workspaces = Workspace::list_with_git(state, self.detailed, &git).await?;
+        Ok(())
+    }
+
+    /// Refresh resource use for every workspace, and git status for those whose files changed, or
+    /// for all of them if it's been `GIT_STATUS_INTERVAL`.
+    /// Worktrees may come and go without container events, so re-list if they have.
+    pub async fn refresh(&mut self, state: &State) -> eyre::Result<()> {
+        let worktrees = worktree::list(&state.project.path).await?;
+        if worktrees != self.worktrees {
+            self.worktrees = worktrees;
+            self.relist(state).await?;
+        }
+
+        if self.refresh_usage(state).await.is_err() {
+            // A container may have gone away since we listed. Its event is likely on the way, but
+            // there's no need to wait for it.
+            self.relist(state).await?;
+        }
+        Ok(())
+    }
+
+    async fn refresh_usage(&mut self, state: &State) -> eyre::Result<()> {
+        let volume_sizes = if self.detailed {
+            Some(state.docker.volume_sizes().await?)
+        } else {
+            None
+        };
+
+        let all = self.git_fetched.elapsed() >= GIT_STATUS_INTERVAL;
+        if all {
+            self.git_fetched = Instant::now();
+        }
+        let known = &self.fingerprints;
+        let base = base_branch(&state.project);
+        let futures = self.workspaces.iter_mut().map(async |ws| {
+            let fingerprint = Fingerprint::fetch(&ws.path).await?;
+            if all || known.get(&ws.path) != Some(&fingerprint) {
+                let base = merge_target(ws.root, &base);
+                ws.git_status = GitStatus::fetch(&ws.path, base).await?;
+            }
+            ws.refresh_usage(&state.docker, volume_sizes.as_ref())
+                .await?;
+            eyre::Ok((ws.path.clone(), fingerprint))
+        });
+        let updated = try_join_all(futures).await?;
+        self.fingerprints.extend(updated);
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+//! Choosing which workspaces `dc list` shows, and in what order.
+
+use std::cmp::Reverse;
+
+use bollard::secret::ContainerSummaryStateEnum;
+use clap::{Args, ValueEnum};
+
+use crate::workspace::Workspace;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StatusFilter {
+    /// Some container is running
+    Running,
+    /// There are containers, but none are running
+    Stopped,
+    /// There are no containers
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Newest first
+    Created,
+    /// Most memory first
+    Mem,
+    Name,
+    /// Most active first, e.g. running before stopped
+    Status,
+}
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct Query {
+    /// Only list workspaces in this state
+    #[arg(long, value_enum)]
+    pub status: Option<StatusFilter>,
+
+    /// Only list workspaces with uncommitted changes
+    #[arg(long)]
+    pub dirty: bool,
+
+    /// Only list workspaces with containers started by dc
+    #[arg(long)]
+    pub managed: bool,
+
+    /// Sort by this, rather than the root workspace first, then by name
+    #[arg(long, value_enum)]
+    pub sort: Option<SortKey>,
+}
+
+impl Query {
+    pub fn matches(&self, ws: &Workspace) -> bool {
+        let status = match self.status {
+            None => true,
+            Some(StatusFilter::Running) => ws.is_running(),
+            Some(StatusFilter::Stopped) => !ws.containers.is_empty() && !ws.is_running(),
+            Some(StatusFilter::None) => ws.containers.is_empty(),
+        };
+        status && (!self.dirty || ws.is_dirty()) && (!self.managed || ws.dc_managed)
+    }
+
+    /// The matching `workspaces`, in order.
+    pub fn apply<'a>(
+        &self,
+        workspaces: impl IntoIterator<Item = &'a Workspace>,
+    ) -> Vec<&'a Workspace> {
+        let mut workspaces: Vec<_> = workspaces
+            .into_iter()
+            .filter(|ws| self.matches(ws))
+            .collect();
+        // Sort by name first, so it breaks ties in the stable sorts below.
+        Workspace::sort(&mut workspaces);
+        match self.sort {
+            None => {}
+            Some(SortKey::Name) => workspaces.sort_by(|a, b| a.name.cmp(&b.name)),
+            Some(SortKey::Created) => workspaces.sort_by_key(|ws| Reverse(ws.created())),
+            Some(SortKey::Mem) => workspaces.sort_by_key(|ws| Reverse(ws.stats.ram)),
+            Some(SortKey::Status) => workspaces.sort_by_key(|ws| Reverse(activity(ws.status()))),
+        }
+        workspaces
+    }
+}
+
+/// How active a container in `state` is, for picking a workspace's overall state and sorting by it.
+pub fn activity(state: ContainerSummaryStateEnum) -> u8 {
+    match state {
+        ContainerSummaryStateEnum::EMPTY => 0,
+        ContainerSummaryStateEnum::DEAD => 1,
+        ContainerSummaryStateEnum::EXITED => 2,
+        ContainerSummaryStateEnum::REMOVING => 3,
+        ContainerSummaryStateEnum::CREATED => 4,
+        ContainerSummaryStateEnum::PAUSED => 5,
+        ContainerSummaryStateEnum::RESTARTING => 6,
+        ContainerSummaryStateEnum::RUNNING => 7,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::{ContainerInfo, Stats};
+    use crate::workspace::git_status::GitStatus;
+
+    fn workspace(name: &str, state: Option<ContainerSummaryStateEnum>, ram: u64) -> Workspace {
+        let containers = state
+            .map(|state| ContainerInfo {
+                id: name.into(),
+                state,
+                local_folder: name.into(),
+                dc_project: None,
+                created: None,
+                host_ports: Vec::new(),
+                profiles: Vec::new(),
+                compose_files: Vec::new(),
+                uses_shared: false,
+                service: None,
+                primary: true,
+            })
+            .into_iter()
+            .collect();
+        Workspace {
+            path: name.into(),
+            name: name.into(),
+            root: name == "root",
+            compose_project_name: format!("{name}_devcontainer"),
+            containers,
+            git_status: GitStatus::default(),
+            execs: Vec::new(),
+            stats: Stats {
+                ram,
+                ..Default::default()
+            },
+            volume_size: None,
+            fwd_ports: Vec::new(),
+            docker_ports: Vec::new(),
+            dc_managed: false,
+            uses_shared: false,
+            worktree: None,
+        }
+    }
+
+    fn names(workspaces: Vec<&Workspace>) -> Vec<&str> {
+        workspaces.into_iter().map(|ws| ws.name.as_str()).collect()
+    }
+
+    #[test]
+    fn filter_and_sort() {
+        let workspaces = [
+            workspace("b", Some(ContainerSummaryStateEnum::EXITED), 0),
+            workspace("root", Some(ContainerSummaryStateEnum::RUNNING), 10),
+            workspace("a", None, 0),
+            workspace("c", Some(ContainerSummaryStateEnum::RUNNING), 20),
+        ];
+
+        assert_eq!(
+            names(Query::default().apply(&workspaces)),
+            ["root", "a", "b", "c"]
+        );
+
+        let running = Query {
+            status: Some(StatusFilter::Running),
+            ..Default::default()
+        };
+        assert_eq!(names(running.apply(&workspaces)), ["root", "c"]);
+
+        let stopped = Query {
+            status: Some(StatusFilter::Stopped),
+            ..Default::default()
+        };
+        assert_eq!(names(stopped.apply(&workspaces)), ["b"]);
+
+        let by_mem = Query {
+            sort: Some(SortKey::Mem),
+            ..Default::default()
+        };
+        assert_eq!(names(by_mem.apply(&workspaces)), ["c", "root", "a", "b"]);
+
+        let by_status = Query {
+            sort: Some(SortKey::Status),
+            ..Default::default()
+        };
+        assert_eq!(names(by_status.apply(&workspaces)), ["root", "c", "b", "a"]);
+    }
+}
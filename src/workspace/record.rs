@@ -0,0 +1,384 @@
+//! Machine-readable output for `dc list`.
+//!
+//! The records here are a stable schema: add fields freely, but don't rename or remove them.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use bollard::secret::ContainerSummaryStateEnum;
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::docker::ExecSession;
+use crate::workspace::Workspace;
+use crate::workspace::git_status::{GitStatus, Merged};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// A human-readable table, on stderr
+    #[default]
+    Table,
+    /// A JSON array of workspaces
+    Json,
+    /// One JSON object per line, per workspace
+    Jsonl,
+    /// Tab-separated values with a header row
+    Tsv,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceRecord<'a> {
+    pub project: &'a str,
+    pub name: &'a str,
+    pub path: &'a PathBuf,
+    pub root: bool,
+    /// The most active state of any container, or `none` if there are none.
+    pub status: String,
+    pub containers: Vec<ContainerRecord<'a>>,
+    pub git: GitRecord<'a>,
+    pub execs: &'a [ExecSession],
+    /// Memory use in bytes.
+    pub ram: u64,
+    pub fwd_ports: &'a [u16],
+    pub docker_ports: &'a [u16],
+    pub dc_managed: bool,
+    pub uses_shared: bool,
+    /// Unix timestamp of the oldest container's creation.
+    pub created: Option<i64>,
+    /// Detailed resource usage, only collected with `--stats`.
+    pub usage: Option<UsageRecord>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageRecord {
+    /// CPU use, as a percentage of one CPU.
+    pub cpu: f64,
+    /// The rest are in bytes.
+    pub block_read: u64,
+    pub block_write: u64,
+    pub net_rx: u64,
+    pub net_tx: u64,
+    pub disk: u64,
+    pub volumes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContainerRecord<'a> {
+    pub id: &'a str,
+    pub state: String,
+    /// The compose service the container runs.
+    pub service: Option<&'a str>,
+    /// Whether it runs the devcontainer's primary service.
+    pub primary: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitRecord<'a> {
+    pub dirty: bool,
+    /// Why the worktree is locked, if it is. The reason may be empty.
+    pub locked: Option<&'a str>,
+    /// Why `git worktree prune` would remove the worktree, if it would.
+    pub prunable: Option<&'a str>,
+    #[serde(flatten)]
+    pub status: &'a GitStatus,
+}
+
+fn state_str(state: ContainerSummaryStateEnum) -> String {
+    match state {
+        ContainerSummaryStateEnum::EMPTY => "none".into(),
+        state => state.to_string(),
+    }
+}
+
+impl<'a> WorkspaceRecord<'a> {
+    pub fn new(project: &'a str, ws: &'a Workspace) -> Self {
+        WorkspaceRecord {
+            project,
+            name: &ws.name,
+            path: &ws.path,
+            root: ws.root,
+            status: state_str(ws.status()),
+            containers: ws
+                .containers
+                .iter()
+                .map(|c| ContainerRecord {
+                    id: &c.id,
+                    state: state_str(c.state),
+                    service: c.service.as_deref(),
+                    primary: c.primary,
+                })
+                .collect(),
+            git: GitRecord {
+                dirty: ws.is_dirty(),
+                locked: ws.worktree.as_ref().and_then(|wt| wt.locked.as_deref()),
+                prunable: ws.worktree.as_ref().and_then(|wt| wt.prunable.as_deref()),
+                status: &ws.git_status,
+            },
+            execs: &ws.execs,
+            ram: ws.stats.ram,
+            fwd_ports: &ws.fwd_ports,
+            docker_ports: &ws.docker_ports,
+            dc_managed: ws.dc_managed,
+            uses_shared: ws.uses_shared,
+            created: ws.created(),
+            usage: ws.volume_size.map(|volumes| UsageRecord {
+                cpu: ws.stats.cpu,
+                block_read: ws.stats.block_read,
+                block_write: ws.stats.block_write,
+                net_rx: ws.stats.net_rx,
+                net_tx: ws.stats.net_tx,
+                disk: ws.stats.disk,
+                volumes,
+            }),
+        }
+    }
+}
+
+const TSV_HEADER: &[&str] = &[
+    "name",
+    "path",
+    "root",
+    "status",
+    "containers",
+    "dirty",
+    "ahead",
+    "behind",
+    "staged",
+    "modified",
+    "deleted",
+    "untracked",
+    "conflicted",
+    "renamed",
+    "execs",
+    "ram",
+    "fwd_ports",
+    "docker_ports",
+    "dc_managed",
+    "uses_shared",
+    "created",
+    "project",
+    "cpu",
+    "block_read",
+    "block_write",
+    "net_rx",
+    "net_tx",
+    "disk",
+    "volumes",
+    "branch",
+    "upstream",
+    "last_commit",
+    "last_commit_time",
+    "stashes",
+    "locked",
+    "prunable",
+    "merged",
+];
+
+impl WorkspaceRecord<'_> {
+    fn tsv_row(&self) -> Vec<String> {
+        let git = self.git.status;
+        let usage = match &self.usage {
+            Some(u) => [
+                u.cpu.to_string(),
+                u.block_read.to_string(),
+                u.block_write.to_string(),
+                u.net_rx.to_string(),
+                u.net_tx.to_string(),
+                u.disk.to_string(),
+                u.volumes.to_string(),
+            ],
+            None => Default::default(),
+        };
+        let mut row = vec![
+            self.name.to_string(),
+            self.path.display().to_string(),
+            self.root.to_string(),
+            self.status.clone(),
+            self.containers.len().to_string(),
+            self.git.dirty.to_string(),
+            git.ahead.to_string(),
+            git.behind.to_string(),
+            git.staged.to_string(),
+            git.modified.to_string(),
+            git.deleted.to_string(),
+            git.untracked.to_string(),
+            git.conflicted.to_string(),
+            git.renamed.to_string(),
+            self.execs.len().to_string(),
+            self.ram.to_string(),
+            self.fwd_ports.iter().join(","),
+            self.docker_ports.iter().join(","),
+            self.dc_managed.to_string(),
+            self.uses_shared.to_string(),
+            self.created.map(|c| c.to_string()).unwrap_or_default(),
+            self.project.to_string(),
+        ];
+        row.extend(usage);
+        let (last_commit, last_commit_time) = match &git.last_commit {
+            Some(c) => (c.subject.clone(), c.time.to_string()),
+            None => Default::default(),
+        };
+        row.extend([
+            git.branch.clone().unwrap_or_default(),
+            git.upstream.clone().unwrap_or_default(),
+            last_commit,
+            last_commit_time,
+            git.stashes.to_string(),
+            self.git.locked.is_some().to_string(),
+            self.git.prunable.is_some().to_string(),
+            match git.merged {
+                Some(Merged::No) => "no",
+                Some(Merged::Yes) => "yes",
+                Some(Merged::Squashed) => "squashed",
+                Some(Merged::NoCommits) => "no_commits",
+                None => "",
+            }
+            .to_string(),
+        ]);
+        row
+    }
+}
+
+/// Write the given `(project, workspace)`s in a machine-readable `format`.
+pub fn write_records<'a>(
+    out: &mut impl Write,
+    format: Format,
+    workspaces: impl IntoIterator<Item = (&'a str, &'a Workspace)>,
+) -> eyre::Result<()> {
+    let records: Vec<WorkspaceRecord> = workspaces
+        .into_iter()
+        .map(|(project, ws)| WorkspaceRecord::new(project, ws))
+        .collect();
+    match format {
+        Format::Table => eyre::bail!("table is not a record format"),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, &records)?;
+            writeln!(out)?;
+        }
+        Format::Jsonl => {
+            for r in &records {
+                serde_json::to_writer(&mut *out, r)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Tsv => {
+            writeln!(out, "{}", TSV_HEADER.join("\t"))?;
+            for r in &records {
+                // Tabs and newlines can't appear in a TSV field.
+                let row = r
+                    .tsv_row()
+                    .into_iter()
+                    .map(|f| f.replace(['\t', '\n'], " "))
+                    .join("\t");
+                writeln!(out, "{row}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::Stats;
+    use crate::workspace::git_status::LastCommit;
+    use crate::worktree::Worktree;
+
+    fn workspace() -> Workspace {
+        Workspace {
+            path: "/tmp/feature".into(),
+            name: "feature".into(),
+            root: false,
+            compose_project_name: "feature_devcontainer".into(),
+            containers: Vec::new(),
+            git_status: GitStatus {
+                modified: 2,
+                branch: Some("feature".into()),
+                upstream: Some("origin/feature".into()),
+                last_commit: Some(LastCommit {
+                    subject: "Add the feature".into(),
+                    time: 1700000000,
+                }),
+                merged: Some(Merged::Squashed),
+                ..Default::default()
+            },
+            execs: Vec::new(),
+            stats: Stats {
+                ram: 1024,
+                ..Default::default()
+            },
+            volume_size: None,
+            fwd_ports: vec![3000],
+            docker_ports: vec![5432, 6379],
+            dc_managed: true,
+            uses_shared: false,
+            worktree: Some(Worktree {
+                path: "/tmp/feature".into(),
+                branch: Some("feature".into()),
+                locked: Some("in use".into()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn jsonl_schema() {
+        let ws = workspace();
+        let mut out = Vec::new();
+        write_records(&mut out, Format::Jsonl, [("best", &ws)]).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "project": "best",
+                "name": "feature",
+                "path": "/tmp/feature",
+                "root": false,
+                "status": "none",
+                "containers": [],
+                "git": {
+                    "dirty": true,
+                    "locked": "in use",
+                    "prunable": null,
+                    "ahead": 0,
+                    "behind": 0,
+                    "staged": 0,
+                    "modified": 2,
+                    "deleted": 0,
+                    "untracked": 0,
+                    "conflicted": 0,
+                    "renamed": 0,
+                    "branch": "feature",
+                    "upstream": "origin/feature",
+                    "last_commit": {"subject": "Add the feature", "time": 1700000000},
+                    "stashes": 0,
+                    "merged": "squashed",
+                },
+                "execs": [],
+                "ram": 1024,
+                "fwd_ports": [3000],
+                "docker_ports": [5432, 6379],
+                "dc_managed": true,
+                "uses_shared": false,
+                "created": null,
+                "usage": null,
+            })
+        );
+    }
+
+    #[test]
+    fn tsv() {
+        let ws = workspace();
+        let mut out = Vec::new();
+        write_records(&mut out, Format::Tsv, [("best", &ws)]).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(lines.next().unwrap(), TSV_HEADER.join("\t"));
+        assert_eq!(
+            lines.next().unwrap(),
+            "feature\t/tmp/feature\tfalse\tnone\t0\ttrue\t0\t0\t0\t2\t0\t0\t0\t0\t0\t1024\t3000\t5432,6379\ttrue\tfalse\t\tbest\t\t\t\t\t\t\t\tfeature\torigin/feature\tAdd the feature\t1700000000\t0\ttrue\tfalse\tsquashed"
+        );
+        assert_eq!(lines.next(), None);
+    }
+}
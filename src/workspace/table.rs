@@ -1,12 +1,193 @@
 use bollard::secret::ContainerSummaryStateEnum;
+use clap::ValueEnum;
+use itertools::Itertools;
 use owo_colors::OwoColorize;
 use tabular::{Row, Table};
 
-use crate::{bytes::format_bytes, workspace::Workspace};
+use crate::{
+    bytes::format_bytes,
+    workspace::{Workspace, git_status::Merged, query::Query},
+};
 
-const TABLE_SPEC: &str = "{:<}  {:<}  {:<}  {:>}  {:>}  {:>}  {:<}  {:<}";
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    Name,
+    /// The checked-out branch, and whether the worktree is locked or prunable
+    Branch,
+    Status,
+    /// Whether dc started the containers
+    Dc,
+    /// Use of the root workspace's shared services
+    Shared,
+    Created,
+    Mem,
+    /// CPU use; needs --stats
+    Cpu,
+    /// Block IO read/written; needs --stats
+    Io,
+    /// Network received/sent; needs --stats
+    Net,
+    /// Size of containers' writable layers; needs --stats
+    Disk,
+    /// Size of named volumes; needs --stats
+    Vols,
+    Execs,
+    Ports,
+    Git,
+    /// Whether the branch is merged into the base branch, including by squashing
+    Merged,
+    /// Age and subject of the last commit
+    LastCommit,
+}
+
+impl Column {
+    const DEFAULT: &[Column] = &[
+        Column::Name,
+        Column::Branch,
+        Column::Status,
+        Column::Dc,
+        Column::Shared,
+        Column::Created,
+        Column::Mem,
+        Column::Execs,
+        Column::Ports,
+        Column::Git,
+        Column::Merged,
+        Column::LastCommit,
+    ];
+    /// With detailed stats, their columns follow MEM.
+    const DETAILED: &[Column] = &[
+        Column::Name,
+        Column::Branch,
+        Column::Status,
+        Column::Dc,
+        Column::Shared,
+        Column::Created,
+        Column::Mem,
+        Column::Cpu,
+        Column::Io,
+        Column::Net,
+        Column::Disk,
+        Column::Vols,
+        Column::Execs,
+        Column::Ports,
+        Column::Git,
+        Column::Merged,
+        Column::LastCommit,
+    ];
+
+    /// The columns to show when none are chosen.
+    pub fn defaults(detailed: bool) -> &'static [Column] {
+        if detailed {
+            Column::DETAILED
+        } else {
+            Column::DEFAULT
+        }
+    }
+
+    /// Whether this column needs detailed stats.
+    pub fn is_detailed(self) -> bool {
+        matches!(
+            self,
+            Column::Cpu | Column::Io | Column::Net | Column::Disk | Column::Vols
+        )
+    }
 
-fn format_age(created: Option<i64>) -> String {
+    fn header(self) -> &'static str {
+        match self {
+            Column::Name => "NAME",
+            Column::Branch => "BRANCH",
+            Column::Status => "STATUS",
+            Column::Dc => "DC",
+            Column::Shared => "SHARED",
+            Column::Created => "CREATED",
+            Column::Mem => "MEM",
+            Column::Cpu => "CPU",
+            Column::Io => "IO R/W",
+            Column::Net => "NET RX/TX",
+            Column::Disk => "DISK",
+            Column::Vols => "VOLS",
+            Column::Execs => "EXECS",
+            Column::Ports => "PORTS",
+            Column::Git => "GIT",
+            Column::Merged => "MERGED",
+            Column::LastCommit => "LAST COMMIT",
+        }
+    }
+
+    fn spec(self) -> &'static str {
+        match self {
+            Column::Created
+            | Column::Mem
+            | Column::Cpu
+            | Column::Io
+            | Column::Net
+            | Column::Disk
+            | Column::Vols
+            | Column::Execs => "{:>}",
+            Column::Name
+            | Column::Branch
+            | Column::Status
+            | Column::Dc
+            | Column::Shared
+            | Column::Ports
+            | Column::Git
+            | Column::Merged
+            | Column::LastCommit => "{:<}",
+        }
+    }
+
+    /// This column's cell for `ws`, where `dependents` workspaces use the project's shared
+    /// services.
+    fn cell(self, ws: &Workspace, dependents: usize) -> String {
+        let stats = &ws.stats;
+        match self {
+            Column::Name => ws.name.clone(),
+            Column::Branch => branch_cell(ws),
+            Column::Status => state_cell(ws.status()),
+            Column::Dc => if ws.dc_managed { "\u{2713}" } else { "" }.into(),
+            Column::Shared => shared_cell(ws, dependents),
+            Column::Created => format_age(ws.created()),
+            Column::Mem => format_nonzero(stats.ram),
+            Column::Cpu if ws.containers.is_empty() => String::new(),
+            Column::Cpu => format!("{:.1}%", stats.cpu),
+            Column::Io => format_pair(stats.block_read, stats.block_write),
+            Column::Net => format_pair(stats.net_rx, stats.net_tx),
+            Column::Disk => format_nonzero(stats.disk),
+            Column::Vols => format_nonzero(ws.volume_size.unwrap_or_default()),
+            Column::Execs if ws.execs.is_empty() => String::new(),
+            Column::Execs => ws.execs.len().to_string(),
+            Column::Ports => {
+                let fwd = ws.fwd_ports.iter().map(|p| p.blue().to_string());
+                let docker = ws.docker_ports.iter().map(ToString::to_string);
+                fwd.chain(docker).join(",")
+            }
+            Column::Git => ws.git_status.to_string(),
+            Column::Merged => match ws.git_status.merged {
+                Some(Merged::Yes) => "\u{2713}".green().to_string(),
+                Some(Merged::Squashed) => "squashed".green().to_string(),
+                Some(Merged::No | Merged::NoCommits) | None => String::new(),
+            },
+            Column::LastCommit => match &ws.git_status.last_commit {
+                Some(commit) => format!(
+                    "{} {}",
+                    format_age(Some(commit.time)),
+                    truncate(&commit.subject, SUBJECT_WIDTH)
+                ),
+                None => String::new(),
+            },
+        }
+    }
+}
+
+/// How much of a commit's subject to show.
+const SUBJECT_WIDTH: usize = 40;
+
+fn table_spec(columns: &[Column]) -> String {
+    columns.iter().map(|c| c.spec()).join("  ")
+}
+
+pub fn format_age(created: Option<i64>) -> String {
     let ts = match created {
         Some(secs) => jiff::Timestamp::from_second(secs).ok(),
         None => None,
@@ -32,19 +213,9 @@ fn format_age(created: Option<i64>) -> String {
     }
 }
 
-struct WsFields {
-    name: String,
-    status: String,
-    created: String,
-    mem: String,
-    ports: String,
-    git: String,
-}
-
-fn ws_fields(ws: &Workspace) -> WsFields {
-    let name = ws.name.clone();
-    let state = ws.status();
-    let status = match state {
+/// A container state, colored by how healthy it is.
+pub fn state_cell(state: ContainerSummaryStateEnum) -> String {
+    match state {
         ContainerSummaryStateEnum::EMPTY => "-".dimmed().to_string(),
         ContainerSummaryStateEnum::RUNNING => state.green().to_string(),
         ContainerSummaryStateEnum::EXITED | ContainerSummaryStateEnum::DEAD => {
@@ -54,69 +225,108 @@ fn ws_fields(ws: &Workspace) -> WsFields {
         | ContainerSummaryStateEnum::PAUSED
         | ContainerSummaryStateEnum::RESTARTING
         | ContainerSummaryStateEnum::REMOVING => state.yellow().to_string(),
+    }
+}
+
+/// For the root, the number of workspaces using its shared services. For others, whether they use
+/// them.
+fn shared_cell(ws: &Workspace, dependents: usize) -> String {
+    if ws.root && dependents > 0 {
+        dependents.to_string()
+    } else if ws.uses_shared {
+        "\u{2713}".into()
+    } else {
+        String::new()
+    }
+}
+
+/// The checked-out branch, marked if the worktree is locked or prunable.
+fn branch_cell(ws: &Workspace) -> String {
+    // A prunable worktree is gone, so only git's list of worktrees knows its branch.
+    let worktree_branch = || ws.worktree.as_ref()?.branch.as_ref();
+    let branch = ws.git_status.branch.as_ref().or_else(worktree_branch);
+    let mut cell = match branch {
+        Some(branch) => branch.clone(),
+        None if ws.worktree.as_ref().is_some_and(|wt| wt.detached) => {
+            "(detached)".dimmed().to_string()
+        }
+        None => String::new(),
     };
-    let mem = match ws.stats.ram {
-        0 => String::new(),
-        ram => format_bytes(ram),
-    };
-    let ports = {
-        let mut parts: Vec<String> = Vec::new();
-        for p in &ws.fwd_ports {
-            parts.push(p.blue().to_string());
+    if let Some(wt) = &ws.worktree {
+        if wt.locked.is_some() {
+            cell.push_str(&" locked".yellow().to_string());
         }
-        for p in &ws.docker_ports {
-            parts.push(p.to_string());
+        if wt.prunable.is_some() {
+            cell.push_str(&" prunable".red().to_string());
         }
-        parts.join(",")
-    };
-    WsFields {
-        name,
-        status,
-        created: format_age(ws.created()),
-        mem,
-        ports,
-        git: ws.git_status.to_string(),
     }
+    cell
 }
 
-fn ws_row(ws: &Workspace) -> Row {
-    let f = ws_fields(ws);
-    let execs = if ws.execs.is_empty() {
+/// `s`, cut to `width` characters with an ellipsis if it's longer.
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.into()
+    } else {
+        let mut s: String = s.chars().take(width - 1).collect();
+        s.push('\u{2026}');
+        s
+    }
+}
+
+/// Format a pair of byte counts as `a/b`, or nothing if both are zero.
+fn format_pair(a: u64, b: u64) -> String {
+    if a == 0 && b == 0 {
         String::new()
     } else {
-        ws.execs.len().to_string()
-    };
-    let dc = if ws.dc_managed { "\u{2713}" } else { "" };
-    Row::new()
-        .with_cell(f.name)
-        .with_ansi_cell(f.status)
-        .with_cell(dc)
-        .with_cell(f.created)
-        .with_ansi_cell(f.mem)
-        .with_cell(execs)
-        .with_ansi_cell(f.ports)
-        .with_ansi_cell(f.git)
+        format!("{}/{}", format_bytes(a), format_bytes(b))
+    }
 }
 
-/// Full table with header row, for `list` output.
-pub fn workspace_table<'a>(workspaces: impl IntoIterator<Item = &'a Workspace>) -> Table {
-    let mut workspaces: Vec<_> = workspaces.into_iter().collect();
-    workspaces.sort_by(|a, b| b.root.cmp(&a.root).then_with(|| a.name.cmp(&b.name)));
-
-    let mut table = Table::new(TABLE_SPEC);
-    table.add_row(
-        Row::new()
-            .with_cell("NAME")
-            .with_cell("STATUS")
-            .with_cell("DC")
-            .with_cell("CREATED")
-            .with_cell("MEM")
-            .with_cell("EXECS")
-            .with_cell("PORTS")
-            .with_cell("GIT"),
-    );
-    for ws in workspaces {
-        table.add_row(ws_row(ws));
+fn format_nonzero(bytes: u64) -> String {
+    match bytes {
+        0 => String::new(),
+        b => format_bytes(b),
+    }
+}
+
+/// Add a workspace's cells to `row`.
+fn ws_row(row: Row, ws: &Workspace, dependents: usize, columns: &[Column]) -> Row {
+    columns
+        .iter()
+        .fold(row, |row, c| row.with_ansi_cell(c.cell(ws, dependents)))
+}
+
+fn header(row: Row, columns: &[Column]) -> Row {
+    columns.iter().fold(row, |row, c| row.with_cell(c.header()))
+}
+
+/// Full table with header row, for `list` output, of the `workspaces` that match `query`.
+pub fn workspace_table(workspaces: &[Workspace], query: &Query, columns: &[Column]) -> Table {
+    let dependents = workspaces.iter().filter(|ws| ws.uses_shared).count();
+
+    let mut table = Table::new(&table_spec(columns));
+    table.add_row(header(Row::new(), columns));
+    for ws in query.apply(workspaces) {
+        table.add_row(ws_row(Row::new(), ws, dependents, columns));
+    }
+    table
+}
+
+/// Like `workspace_table`, but for several projects, with a PROJECT column.
+pub fn projects_table<'a>(
+    projects: impl IntoIterator<Item = (&'a str, &'a [Workspace])>,
+    query: &Query,
+    columns: &[Column],
+) -> Table {
+    let mut table = Table::new(&format!("{{:<}}  {}", table_spec(columns)));
+    table.add_row(header(Row::new().with_cell("PROJECT"), columns));
+    for (project, workspaces) in projects {
+        let dependents = workspaces.iter().filter(|ws| ws.uses_shared).count();
+        for ws in query.apply(workspaces) {
+            let row = Row::new().with_cell(project);
+            table.add_row(ws_row(row, ws, dependents, columns));
+        }
     }
     table
 }
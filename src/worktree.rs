@@ -4,13 +4,69 @@ use std::process::Output;
 use eyre::WrapErr;
 use tokio::process::Command;
 
+use crate::devcontainer::dc_options::CopyFile;
 use crate::run::run_cmd;
 
+/// What a new worktree checks out.
+#[derive(Debug, Clone, Default)]
+pub enum Checkout {
+    /// The branch named after the workspace, created from HEAD if it doesn't exist.
+    #[default]
+    Default,
+    /// A new branch named after the workspace, starting at the given ref.
+    From(String),
+    /// An existing branch.
+    Branch(String),
+    /// A new branch tracking the given remote branch, e.g. `origin/feature-x`, after fetching it.
+    /// The new branch is named after the remote one, e.g. `feature-x`.
+    Track(String),
+    /// A detached HEAD, at the given ref or HEAD.
+    Detach(Option<String>),
+}
+
+impl Checkout {
+    /// The existing local branch this would check out, if any, where `branch` is the one named
+    /// after the workspace.
+    fn existing_branch<'a>(&'a self, branch: &'a str) -> eyre::Result<Option<&'a str>> {
+        Ok(match self {
+            Checkout::Default => Some(branch),
+            Checkout::Branch(branch) => Some(branch),
+            Checkout::Track(remote_branch) => Some(split_remote(remote_branch)?.1),
+            Checkout::From(_) | Checkout::Detach(_) => None,
+        })
+    }
+}
+
+/// Split e.g. `origin/feature-x` into its remote and branch.
+fn split_remote(remote_branch: &str) -> eyre::Result<(&str, &str)> {
+    remote_branch
+        .split_once('/')
+        .ok_or_else(|| eyre::eyre!("expected <remote>/<branch>, got {remote_branch:?}"))
+}
+
+/// The directory name for a workspace called `name`, which may be a branch-style name like
+/// `user/thing`.
+pub fn dir_name(name: &str) -> eyre::Result<String> {
+    if name
+        .split('/')
+        .any(|part| Path::new(part).file_name().is_none_or(|f| f != part))
+    {
+        eyre::bail!("invalid workspace name: {name:?}");
+    }
+    Ok(name.replace('/', "-"))
+}
+
+/// Create the worktree for the workspace `name`, a directory name from `dir_name`, unless it
+/// exists. `requested` is the name it was asked for by, which may be branch-style, and is kept
+/// with `set_name`. `branch` is the one named after the workspace, for `checkout` to create or
+/// use.
 pub async fn create(
     repo_path: &Path,
     workspace_dir: &Path,
     name: &str,
-    detach: bool,
+    requested: &str,
+    branch: &str,
+    checkout: &Checkout,
 ) -> eyre::Result<PathBuf> {
     if Path::new(name).file_name().is_none_or(|f| f != name) {
         eyre::bail!("invalid workspace name: {name:?}");
@@ -30,17 +86,110 @@ pub async fn create(
         if wt_common != repo_common {
             eyre::bail!("existing repository at {worktree_path_str}");
         }
-    } else {
-        let mut args = vec!["git", "worktree", "add", &worktree_path_str];
-        if detach {
-            args.push("--detach");
+        // Names like `a/b` and `a-b` share a directory, so make sure it's this workspace's.
+        let expected = match checkout {
+            Checkout::Detach(_) => None,
+            _ => Some(checkout.existing_branch(branch)?.unwrap_or(branch)),
+        };
+        if requested != name
+            && let Some(wt) = list(repo_path)
+                .await?
+                .into_iter()
+                .find(|wt| wt.path == worktree_path)
+            && wt.name.as_deref() != Some(requested)
+            && wt.branch.as_deref() != expected
+        {
+            let owner = wt.name.or(wt.branch).unwrap_or_else(|| name.to_string());
+            eyre::bail!(
+                "{worktree_path_str} already belongs to workspace {owner}; choose another name"
+            );
+        }
+        return Ok(worktree_path);
+    }
+
+    // Git refuses to check out a branch in two worktrees. Say where, as its error isn't shown.
+    if let Some(existing) = checkout.existing_branch(branch)?
+        && let Some(wt) = list(repo_path)
+            .await?
+            .into_iter()
+            .find(|wt| wt.branch.as_deref() == Some(existing))
+    {
+        eyre::bail!(
+            "branch {existing} is already checked out at {}",
+            wt.path.display()
+        );
+    }
+
+    let mut args = vec!["git", "worktree", "add"];
+    match checkout {
+        Checkout::Default => {
+            let local = format!("refs/heads/{branch}");
+            if repo.try_find_reference(local.as_str())?.is_some() {
+                args.extend([&*worktree_path_str, branch]);
+            } else {
+                args.extend(["-b", branch, &worktree_path_str]);
+            }
+        }
+        Checkout::From(from) => args.extend(["-b", branch, &worktree_path_str, from]),
+        Checkout::Branch(branch) => args.extend([&*worktree_path_str, branch]),
+        Checkout::Track(remote_branch) => {
+            let (remote, branch) = split_remote(remote_branch)?;
+            // Git refuses to create it again, without saying why where we'd show it.
+            let local = format!("refs/heads/{branch}");
+            if repo.try_find_reference(local.as_str())?.is_some() {
+                eyre::bail!(
+                    "branch {branch} already exists; check it out with --branch {branch} instead"
+                );
+            }
+            run_cmd(&["git", "fetch", remote, branch], Some(repo_path)).await?;
+            args.extend(["--track", "-b", branch, &worktree_path_str, remote_branch]);
+        }
+        Checkout::Detach(from) => {
+            args.extend(["--detach", &worktree_path_str]);
+            args.extend(from.as_deref());
         }
-        run_cmd(&args, Some(repo_path)).await?;
     }
+    run_cmd(&args, Some(repo_path)).await?;
+    set_name(repo_path, name, (requested != name).then_some(requested)).await?;
 
     Ok(worktree_path)
 }
 
+/// The git config key holding the name the workspace in the directory `dir` was created with,
+/// when that isn't `dir` itself, e.g. `user/thing` for `user-thing`.
+fn name_key(dir: &str) -> String {
+    format!("dc.{dir}.name")
+}
+
+/// Keep `name` as the name of the workspace in the directory `dir`, or forget it if `None`.
+pub async fn set_name(repo_path: &Path, dir: &str, name: Option<&str>) -> eyre::Result<()> {
+    let key = name_key(dir);
+    match name {
+        Some(name) => run_cmd(&["git", "config", &key, name], Some(repo_path)).await,
+        None => {
+            let repo = gix::open(repo_path)?;
+            if repo.config_snapshot().string(key.as_str()).is_none() {
+                return Ok(());
+            }
+            run_cmd(&["git", "config", "--unset", &key], Some(repo_path)).await
+        }
+    }
+}
+
+/// Recreate a worktree whose directory is gone, e.g. from `/tmp` after a reboot, checking out what
+/// it had.
+pub async fn recreate(repo_path: &Path, wt: &Worktree) -> eyre::Result<()> {
+    let path = wt.path.to_string_lossy();
+    // Git refuses to add a worktree that's missing but still registered, unless forced.
+    let mut args = vec!["git", "worktree", "add", "--force"];
+    match (&wt.branch, &wt.head) {
+        (Some(branch), _) => args.extend([&*path, branch]),
+        (None, Some(head)) => args.extend(["--detach", &path, head]),
+        (None, None) => eyre::bail!("worktree {path} has no commit to check out"),
+    }
+    run_cmd(&args, Some(repo_path)).await
+}
+
 async fn worktree_list(repo_path: &Path) -> eyre::Result<Output> {
     Command::new("git")
         .args(["worktree", "list", "--porcelain"])
@@ -59,24 +208,502 @@ fn worktree_list_sync(repo_path: &Path) -> eyre::Result<Output> {
         .map_err(Into::into)
 }
 
-fn process_list(out: Output) -> eyre::Result<Vec<PathBuf>> {
+/// A worktree, as described by `git worktree list --porcelain`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Worktree {
+    pub path: PathBuf,
+    /// The commit HEAD is at, unless the repo has no commits yet.
+    pub head: Option<String>,
+    /// The checked-out branch, e.g. `main`, unless HEAD is detached.
+    pub branch: Option<String>,
+    pub detached: bool,
+    /// Why the worktree is locked, if it is. The reason may be empty.
+    pub locked: Option<String>,
+    /// Why `git worktree prune` would remove the worktree, if it would.
+    pub prunable: Option<String>,
+    /// The name its workspace was created with, if that isn't its directory's, from `set_name`.
+    pub name: Option<String>,
+}
+
+fn process_list(out: Output, repo_path: &Path) -> eyre::Result<Vec<Worktree>> {
     eyre::ensure!(out.status.success(), "git worktree list failed");
     let output =
         String::from_utf8(out.stdout).wrap_err("git worktree list output is not valid UTF-8")?;
 
-    Ok(output
-        .lines()
-        .filter_map(|line| line.strip_prefix("worktree ").map(PathBuf::from))
-        .collect())
+    let mut worktrees = parse_list(&output);
+    let repo = gix::open(repo_path)
+        .wrap_err_with(|| format!("failed to open git repo at {}", repo_path.display()))?;
+    let config = repo.config_snapshot();
+    // The main worktree is listed first.
+    if let Some(main) = worktrees.first_mut() {
+        main.locked = config.string(ROOT_LOCK_KEY).map(|r| r.to_string());
+    }
+    for wt in &mut worktrees {
+        if let Some(dir) = wt.path.file_name() {
+            let key = name_key(&dir.to_string_lossy());
+            wt.name = config.string(key.as_str()).map(|n| n.to_string());
+        }
+    }
+    Ok(worktrees)
+}
+
+/// Parse porcelain output, which has a paragraph per worktree, each starting with its path.
+fn parse_list(output: &str) -> Vec<Worktree> {
+    let mut worktrees: Vec<Worktree> = Vec::new();
+    for line in output.lines() {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        if key == "worktree" {
+            worktrees.push(Worktree {
+                path: PathBuf::from(value),
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(worktree) = worktrees.last_mut() else {
+            continue;
+        };
+        match key {
+            "HEAD" => worktree.head = Some(value.to_string()),
+            "branch" => {
+                let branch = value.strip_prefix("refs/heads/").unwrap_or(value);
+                worktree.branch = Some(branch.to_string());
+            }
+            "detached" => worktree.detached = true,
+            "locked" => worktree.locked = Some(value.to_string()),
+            "prunable" => worktree.prunable = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    worktrees
 }
 
-pub async fn list(repo_path: &Path) -> eyre::Result<Vec<PathBuf>> {
+pub async fn list(repo_path: &Path) -> eyre::Result<Vec<Worktree>> {
     let out = worktree_list(repo_path).await?;
-    process_list(out)
+    process_list(out, repo_path)
 }
 
 /// A non-async worktree list for use in the completer.
-pub fn list_sync(repo_path: &Path) -> eyre::Result<Vec<PathBuf>> {
+pub fn list_sync(repo_path: &Path) -> eyre::Result<Vec<Worktree>> {
     let out = worktree_list_sync(repo_path)?;
-    process_list(out)
+    process_list(out, repo_path)
+}
+
+/// A file that `copy_files` copied, or symlinked, into a new worktree.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CopiedFile {
+    /// Its path, relative to the worktree.
+    pub path: PathBuf,
+    pub symlink: bool,
+}
+
+/// Copy the untracked files of the worktree at `from`, such as gitignored `.env` files, that match
+/// any of `patterns`, into the same places in the worktree at `to`, or symlink them there. Files
+/// that already exist in `to` are left alone.
+pub async fn copy_files(
+    from: &Path,
+    to: &Path,
+    patterns: &[CopyFile],
+) -> eyre::Result<Vec<CopiedFile>> {
+    if patterns.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Only list what the globs match, rather than every ignored file, like all of `target/`.
+    let pathspecs = patterns
+        .iter()
+        .map(|p| format!(":(glob){}", p.glob().trim_end_matches('/')));
+    let out = Command::new("git")
+        .args(["ls-files", "--others", "-z", "--"])
+        .args(pathspecs)
+        .current_dir(from)
+        .output()
+        .await?;
+    eyre::ensure!(out.status.success(), "git ls-files failed");
+
+    let mut copied = Vec::new();
+    for file in out.stdout.split(|&b| b == 0) {
+        let Ok(file) = std::str::from_utf8(file) else {
+            continue;
+        };
+        let Some(pattern) = patterns.iter().find(|p| matches_file(p.glob(), file)) else {
+            continue;
+        };
+        let (src, dst) = (from.join(file), to.join(file));
+        if dst.symlink_metadata().is_ok() {
+            continue;
+        }
+        if let Some(parent) = dst.parent() {
+            std::fs::create_dir_all(parent)
+                .wrap_err_with(|| format!("failed to create {}", parent.display()))?;
+        }
+        let result = if pattern.symlink() {
+            std::os::unix::fs::symlink(&src, &dst)
+        } else {
+            std::fs::copy(&src, &dst).map(|_| ())
+        };
+        result
+            .wrap_err_with(|| format!("failed to copy {} to {}", src.display(), dst.display()))?;
+        copied.push(CopiedFile {
+            path: file.into(),
+            symlink: pattern.symlink(),
+        });
+    }
+    Ok(copied)
+}
+
+/// Whether `glob` matches the file at the relative `path`, or one of its directories.
+fn matches_file(glob: &str, path: &str) -> bool {
+    let mode = gix::glob::wildmatch::Mode::NO_MATCH_SLASH_LITERAL;
+    let glob = glob.trim_end_matches('/');
+    path.char_indices()
+        .filter(|&(_, c)| c == '/')
+        .map(|(i, _)| &path[..i])
+        .chain([path])
+        .any(|p| gix::glob::wildmatch(glob.into(), p.into(), mode))
+}
+
+/// The git config key holding why the main worktree is locked, as git can only lock linked ones.
+const ROOT_LOCK_KEY: &str = "dc.lock";
+
+/// Lock the worktree at `path`, protecting it from `dc destroy` and `dc prune`, and, unless it's
+/// the main worktree at `repo_path`, from `git worktree remove` and `git worktree prune`.
+pub async fn lock(repo_path: &Path, path: &Path, reason: Option<&str>) -> eyre::Result<()> {
+    if path == repo_path {
+        let args = ["git", "config", ROOT_LOCK_KEY, reason.unwrap_or_default()];
+        return run_cmd(&args, Some(repo_path)).await;
+    }
+    let path = path.to_string_lossy();
+    let mut args = vec!["git", "worktree", "lock"];
+    if let Some(reason) = reason {
+        args.extend(["--reason", reason]);
+    }
+    args.push(&path);
+    run_cmd(&args, Some(repo_path)).await
+}
+
+/// Unlock the worktree at `path`, locked with `lock`.
+pub async fn unlock(repo_path: &Path, path: &Path) -> eyre::Result<()> {
+    if path == repo_path {
+        let args = ["git", "config", "--unset", ROOT_LOCK_KEY];
+        return run_cmd(&args, Some(repo_path)).await;
+    }
+    let path = path.to_string_lossy();
+    run_cmd(&["git", "worktree", "unlock", &path], Some(repo_path)).await
+}
+
+/// The short names of the repo's local branches, e.g. `main`, or with `remote`, its
+/// remote-tracking branches, e.g. `origin/main`.
+pub fn branches(repo_path: &Path, remote: bool) -> eyre::Result<Vec<String>> {
+    let repo = gix::open(repo_path)?;
+    let references = repo.references()?;
+    let branches = if remote {
+        references.remote_branches()?
+    } else {
+        references.local_branches()?
+    };
+    Ok(branches
+        .filter_map(Result::ok)
+        .map(|r| r.name().shorten().to_string())
+        // The remote's default branch, which is just a pointer to another.
+        .filter(|name| !name.ends_with("/HEAD"))
+        .collect())
+}
+
+/// The remote, and the branch on it, that the local `branch` tracks, if that remote is a repository
+/// on this machine, such as a bare repository.
+pub fn local_upstream(repo_path: &Path, branch: &str) -> eyre::Result<Option<(String, String)>> {
+    let repo = gix::open(repo_path)?;
+    let full_name: gix::refs::FullName = format!("refs/heads/{branch}").try_into()?;
+    let direction = gix::remote::Direction::Fetch;
+    let Some(remote_name) = repo.branch_remote_name(branch, direction) else {
+        return Ok(None);
+    };
+    let Some(remote_name) = remote_name.as_symbol() else {
+        return Ok(None);
+    };
+    let Some(merge) = repo.branch_remote_ref_name(full_name.as_ref(), direction) else {
+        return Ok(None);
+    };
+    let remote = repo.find_remote(remote_name)?;
+    let is_local = remote
+        .url(gix::remote::Direction::Push)
+        .is_some_and(|url| url.scheme == gix::url::Scheme::File);
+    if !is_local {
+        return Ok(None);
+    }
+    Ok(Some((
+        remote_name.to_string(),
+        merge?.shorten().to_string(),
+    )))
+}
+
+/// The branch checked out in the worktree at `path`, or `None` if its HEAD is detached.
+pub fn branch(path: &Path) -> eyre::Result<Option<String>> {
+    let repo = gix::open(path)
+        .wrap_err_with(|| format!("failed to open git repo at {}", path.display()))?;
+    Ok(repo.head_name()?.map(|name| name.shorten().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::test_repo;
+
+    #[test]
+    fn dir_names() {
+        assert_eq!(dir_name("thing").unwrap(), "thing");
+        assert_eq!(
+            dir_name("user/JIRA-123-thing").unwrap(),
+            "user-JIRA-123-thing"
+        );
+        assert!(dir_name("user//thing").is_err());
+        assert!(dir_name("../thing").is_err());
+        assert!(dir_name("thing/").is_err());
+    }
+
+    #[test]
+    fn parse_porcelain() {
+        const HEAD: &str = "8a7c1c6b0b7e3a0c1f6f2d2a5b8e1c9d0e4f3a2b";
+        let output = "\
+worktree /src/best
+HEAD 8a7c1c6b0b7e3a0c1f6f2d2a5b8e1c9d0e4f3a2b
+branch refs/heads/main
+
+worktree /tmp/feature
+HEAD 8a7c1c6b0b7e3a0c1f6f2d2a5b8e1c9d0e4f3a2b
+detached
+locked on a usb drive
+
+worktree /tmp/gone
+HEAD 8a7c1c6b0b7e3a0c1f6f2d2a5b8e1c9d0e4f3a2b
+branch refs/heads/user/gone
+locked
+prunable gitdir file points to non-existent location
+";
+        assert_eq!(
+            parse_list(output),
+            [
+                Worktree {
+                    path: "/src/best".into(),
+                    head: Some(HEAD.into()),
+                    branch: Some("main".into()),
+                    ..Default::default()
+                },
+                Worktree {
+                    path: "/tmp/feature".into(),
+                    head: Some(HEAD.into()),
+                    detached: true,
+                    locked: Some("on a usb drive".into()),
+                    ..Default::default()
+                },
+                Worktree {
+                    path: "/tmp/gone".into(),
+                    head: Some(HEAD.into()),
+                    branch: Some("user/gone".into()),
+                    locked: Some(String::new()),
+                    prunable: Some("gitdir file points to non-existent location".into()),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn local_upstreams() {
+        let (tmp, repo, git) = test_repo();
+        let bare = tmp.path().join("bare");
+
+        git(&["commit", "-q", "--allow-empty", "-m", "a"]);
+        git(&["init", "-q", "--bare", bare.to_str().unwrap()]);
+        git(&["remote", "add", "local", bare.to_str().unwrap()]);
+        git(&[
+            "remote",
+            "add",
+            "github",
+            "https://github.com/paholg/dc.git",
+        ]);
+        git(&["branch", "feature"]);
+        git(&["push", "-q", "-u", "local", "feature:user/feature"]);
+        git(&["branch", "other"]);
+        git(&["config", "branch.other.remote", "github"]);
+        git(&["config", "branch.other.merge", "refs/heads/other"]);
+
+        assert_eq!(
+            local_upstream(&repo, "feature").unwrap(),
+            Some(("local".into(), "user/feature".into()))
+        );
+        assert_eq!(local_upstream(&repo, "other").unwrap(), None);
+        assert_eq!(local_upstream(&repo, "main").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn track_existing_branch() {
+        let (tmp, repo, git) = test_repo();
+        let bare = tmp.path().join("bare");
+        git(&["commit", "-q", "--allow-empty", "-m", "a"]);
+        git(&["init", "-q", "--bare", bare.to_str().unwrap()]);
+        git(&["remote", "add", "local", bare.to_str().unwrap()]);
+        git(&["push", "-q", "local", "main:feature"]);
+        let track = Checkout::Track("local/feature".into());
+
+        let path = create(&repo, tmp.path(), "one", "one", "one", &track)
+            .await
+            .unwrap();
+        assert_eq!(branch(&path).unwrap().as_deref(), Some("feature"));
+        git(&["worktree", "remove", path.to_str().unwrap()]);
+
+        let err = create(&repo, tmp.path(), "two", "two", "two", &track)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("--branch feature"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn lock_and_unlock() {
+        let (tmp, repo, git) = test_repo();
+        let feature = tmp.path().join("feature");
+        git(&["commit", "-q", "--allow-empty", "-m", "a"]);
+        git(&[
+            "worktree",
+            "add",
+            "-qb",
+            "feature",
+            feature.to_str().unwrap(),
+        ]);
+        let locks = async || {
+            let worktrees = list(&repo).await.unwrap();
+            worktrees
+                .into_iter()
+                .map(|wt| wt.locked)
+                .collect::<Vec<_>>()
+        };
+
+        lock(&repo, &repo, None).await.unwrap();
+        lock(&repo, &feature, Some("release")).await.unwrap();
+        assert_eq!(locks().await, [Some(String::new()), Some("release".into())]);
+
+        unlock(&repo, &repo).await.unwrap();
+        unlock(&repo, &feature).await.unwrap();
+        assert_eq!(locks().await, [None, None]);
+    }
+
+    #[tokio::test]
+    async fn recreate_missing() {
+        let (tmp, repo, git) = test_repo();
+        let dir = tmp.path();
+        let (feature, detached) = (dir.join("feature"), dir.join("detached"));
+        git(&["commit", "-q", "--allow-empty", "-m", "a"]);
+        git(&[
+            "worktree",
+            "add",
+            "-qb",
+            "feature",
+            feature.to_str().unwrap(),
+        ]);
+        git(&[
+            "worktree",
+            "add",
+            "-q",
+            "--detach",
+            detached.to_str().unwrap(),
+        ]);
+
+        // As if they were in `/tmp` across a reboot.
+        std::fs::remove_dir_all(&feature).unwrap();
+        std::fs::remove_dir_all(&detached).unwrap();
+        let missing = list(&repo).await.unwrap();
+        assert!(missing[1..].iter().all(|wt| wt.prunable.is_some()));
+        for wt in &missing[1..] {
+            recreate(&repo, wt).await.unwrap();
+        }
+
+        let recreated = list(&repo).await.unwrap();
+        assert!(recreated.iter().all(|wt| wt.prunable.is_none()));
+        assert_eq!(branch(&feature).unwrap().as_deref(), Some("feature"));
+        assert_eq!(branch(&detached).unwrap(), None);
+        assert_eq!(
+            recreated[1..].iter().map(|wt| &wt.head).collect::<Vec<_>>(),
+            missing[1..].iter().map(|wt| &wt.head).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn branch_style_names() {
+        let (tmp, repo, git) = test_repo();
+        let dir = tmp.path();
+        git(&["commit", "-q", "--allow-empty", "-m", "a"]);
+
+        let create = |name: &'static str, requested: &'static str| {
+            let repo = repo.clone();
+            async move {
+                let branch = requested;
+                create(&repo, dir, name, requested, branch, &Checkout::Default).await
+            }
+        };
+        create("user-thing", "user/thing").await.unwrap();
+        let wt = list(&repo).await.unwrap().pop().unwrap();
+        assert_eq!(wt.name.as_deref(), Some("user/thing"));
+        // By either name.
+        create("user-thing", "user/thing").await.unwrap();
+        create("user-thing", "user-thing").await.unwrap();
+
+        create("a-b", "a-b").await.unwrap();
+        let err = create("a-b", "a/b").await.unwrap_err();
+        assert!(err.to_string().contains("already belongs to workspace a-b"));
+
+        set_name(&repo, "user-thing", None).await.unwrap();
+        set_name(&repo, "user-thing", None).await.unwrap();
+        let wt = list(&repo).await.unwrap().remove(1);
+        assert_eq!(wt.name, None);
+    }
+
+    #[tokio::test]
+    async fn copy_untracked_files() {
+        let (tmp, repo, git) = test_repo();
+        let feature = tmp.path().join("feature");
+        std::fs::create_dir_all(repo.join("config/local")).unwrap();
+        std::fs::create_dir_all(&feature).unwrap();
+        for file in [".env", "notes.txt", "config/local/secrets.toml"] {
+            std::fs::write(repo.join(file), file).unwrap();
+        }
+        std::fs::write(repo.join(".gitignore"), ".env*\nconfig/local/\n").unwrap();
+        git(&["add", ".gitignore"]);
+        std::fs::write(feature.join("notes.txt"), "mine").unwrap();
+
+        let patterns = [
+            CopyFile::Glob(".env*".into()),
+            CopyFile::Glob("*.txt".into()),
+            CopyFile::Options {
+                glob: "config/local".into(),
+                symlink: true,
+            },
+        ];
+        let copied = copy_files(&repo, &feature, &patterns).await.unwrap();
+        assert_eq!(
+            copied,
+            [
+                CopiedFile {
+                    path: ".env".into(),
+                    symlink: false,
+                },
+                CopiedFile {
+                    path: "config/local/secrets.toml".into(),
+                    symlink: true,
+                },
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(feature.join(".env")).unwrap(),
+            ".env"
+        );
+        assert_eq!(
+            std::fs::read_link(feature.join("config/local/secrets.toml")).unwrap(),
+            repo.join("config/local/secrets.toml")
+        );
+        // Tracked files are the worktree's own, and existing ones are left alone.
+        assert!(!feature.join(".gitignore").exists());
+        assert_eq!(
+            std::fs::read_to_string(feature.join("notes.txt")).unwrap(),
+            "mine"
+        );
+    }
 }